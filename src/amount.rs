@@ -0,0 +1,60 @@
+// 金额以整数最小单位存储（类似比特币的 satoshi），避免浮点误差
+
+pub const COIN: u64 = 100_000_000;
+pub const DECIMALS: usize = 8;
+
+// 把十进制字符串（如 "1.5"）解析为最小单位
+pub fn parse_amount(s: &str) -> Result<u64, &'static str> {
+    let s = s.trim();
+    if s.starts_with('-') {
+        return Err("Amount cannot be negative");
+    }
+    let (whole, frac) = match s.split_once('.') {
+        Some((whole, frac)) => (whole, frac),
+        None => (s, ""),
+    };
+    if whole.is_empty() && frac.is_empty() {
+        return Err("Amount is empty");
+    }
+    if !whole.bytes().all(|b| b.is_ascii_digit()) || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("Amount must be a decimal number");
+    }
+    if frac.len() > DECIMALS {
+        return Err("Amount has too many decimal places");
+    }
+
+    let whole_units = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<u64>().map_err(|_| "Amount overflows")?
+    };
+    let frac_units = if frac.is_empty() {
+        0
+    } else {
+        format!("{:0<width$}", frac, width = DECIMALS).parse::<u64>().unwrap()
+    };
+
+    whole_units
+        .checked_mul(COIN)
+        .and_then(|units| units.checked_add(frac_units))
+        .ok_or("Amount overflows")
+}
+
+// 把最小单位格式化为固定 8 位小数的字符串
+pub fn format_amount(units: u64) -> String {
+    format!("{}.{:0width$}", units / COIN, units % COIN, width = DECIMALS)
+}
+
+// 在 JSON 中以十进制字符串表示金额: #[serde(with = "amount::decimal")]
+pub mod decimal {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(units: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format_amount(*units))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        super::parse_amount(&s).map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,88 @@
+use serde::Serialize;
+
+use crate::target::U256;
+use crate::BlockHeader;
+
+// 难度调整规则: 每 retarget_interval 个区块根据实际出块时间调整一次目标值
+// 目标值以紧凑格式 (nBits) 存储在区块头中
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct DifficultyRules {
+    pub initial_bits: u32,   // 创世区块的目标值
+    pub pow_limit_bits: u32, // 允许的最大目标值（最低难度）
+    pub retarget_interval: u32,
+    pub target_block_secs: i64,
+    pub retarget: bool, // 为 false 时始终使用初始目标值（regtest）
+}
+
+impl Default for DifficultyRules {
+    fn default() -> Self {
+        DifficultyRules {
+            // 约等于哈希前 4 个十六进制字符为 '0'
+            initial_bits: 0x1f00_ffff,
+            pow_limit_bits: 0x207f_ffff,
+            retarget_interval: 10,
+            target_block_secs: 60,
+            retarget: true,
+        }
+    }
+}
+
+// 单次调整时实际耗时被限制在期望耗时的 [1/4, 4] 倍之间
+const MAX_ADJUSTMENT_FACTOR: i64 = 4;
+
+// 计算中位时间 (median-time-past) 时使用的区块数
+const MEDIAN_TIME_SPAN: usize = 11;
+
+// 区块时间戳最多允许超前本地时间 2 小时
+pub const MAX_FUTURE_BLOCK_SECS: i64 = 2 * 60 * 60;
+
+// chain 最后 11 个区块时间戳的中位数，新区块的时间戳必须严格大于它
+// 空链没有下限，返回 i64::MIN
+pub fn median_time_past<H: AsRef<BlockHeader>>(chain: &[H]) -> i64 {
+    let start = chain.len().saturating_sub(MEDIAN_TIME_SPAN);
+    let mut timestamps: Vec<i64> = chain[start..].iter().map(|h| h.as_ref().timestamp).collect();
+    if timestamps.is_empty() {
+        return i64::MIN;
+    }
+    timestamps.sort_unstable();
+    timestamps[timestamps.len() / 2]
+}
+
+// 哈希前 zeros 个十六进制字符为 '0' 对应的目标值
+pub fn bits_for_leading_zeros(zeros: usize) -> u32 {
+    U256::MAX.shr(4 * zeros.min(64) as u32).to_compact()
+}
+
+impl DifficultyRules {
+    pub fn pow_limit(&self) -> U256 {
+        U256::from_compact(self.pow_limit_bits).unwrap_or(U256::MAX)
+    }
+
+    // 在 chain 之后的下一个区块应使用的目标值，chain 可以是区块或只有区块头
+    pub fn next_bits<H: AsRef<BlockHeader>>(&self, chain: &[H]) -> u32 {
+        let interval = self.retarget_interval.max(2) as usize;
+        let height = chain.len();
+        let Some(last) = chain.last() else {
+            return self.initial_bits;
+        };
+        let last = last.as_ref();
+        if !self.retarget || !height.is_multiple_of(interval) {
+            return last.bits;
+        }
+
+        // 一个调整周期内的 interval 个区块之间有 interval - 1 个出块间隔
+        let first = chain[height - interval].as_ref();
+        let expected = self.target_block_secs.max(1) * (interval as i64 - 1);
+        let actual = (last.timestamp - first.timestamp)
+            .clamp(expected / MAX_ADJUSTMENT_FACTOR, expected * MAX_ADJUSTMENT_FACTOR)
+            .max(1);
+
+        // 新目标值 = 旧目标值 * 实际耗时 / 期望耗时
+        let current = U256::from_compact(last.bits).unwrap_or_else(|| self.pow_limit());
+        let next = match current.checked_mul_u64(actual as u64) {
+            Some(product) => product.div_u64(expected as u64),
+            None => current.div_u64(expected as u64).checked_mul_u64(actual as u64).unwrap_or(U256::MAX),
+        };
+        next.min(self.pow_limit()).to_compact()
+    }
+}
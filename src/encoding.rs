@@ -0,0 +1,262 @@
+// 规范化的二进制编码，所有哈希、签名与存储都基于它
+//
+// 格式（便于其他语言的客户端逐字节复现）:
+// - 整数: 定长大端序 (u8 / u32 / u64 / i64)
+// - 字符串与字节串: u32 长度前缀 + 原始字节 (字符串为 UTF-8)
+// - Option: 1 字节标记 (0 = None, 1 = Some) + 内容
+// - 列表: u32 元素个数 + 依次编码的元素
+
+pub trait Encode {
+    fn encode(&self, w: &mut Writer);
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        self.encode(&mut w);
+        w.into_bytes()
+    }
+}
+
+pub trait Decode: Sized {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str>;
+
+    // 解码完整的字节串，多余的字节视为错误
+    fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        let mut r = Reader::new(bytes);
+        let value = Self::decode(&mut r)?;
+        if !r.is_empty() {
+            return Err("Trailing bytes after value");
+        }
+        Ok(value)
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, w: &mut Writer) {
+        w.put_list(self);
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str> {
+        r.get_list()
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, w: &mut Writer) {
+        w.put_str(self);
+    }
+}
+
+impl Decode for String {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str> {
+        r.get_string()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn put_u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn put_u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn put_u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn put_i64(&mut self, v: i64) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn put_bytes(&mut self, v: &[u8]) {
+        self.put_u32(v.len() as u32);
+        self.buf.extend_from_slice(v);
+    }
+
+    pub fn put_str(&mut self, v: &str) {
+        self.put_bytes(v.as_bytes());
+    }
+
+    pub fn put_option_str(&mut self, v: Option<&str>) {
+        match v {
+            Some(s) => {
+                self.put_u8(1);
+                self.put_str(s);
+            }
+            None => self.put_u8(0),
+        }
+    }
+
+    pub fn put_list<T: Encode>(&mut self, items: &[T]) {
+        self.put_u32(items.len() as u32);
+        for item in items {
+            item.encode(self);
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Reader { buf }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], &'static str> {
+        if self.buf.len() < n {
+            return Err("Unexpected end of input");
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64, &'static str> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn get_i64(&mut self) -> Result<i64, &'static str> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn get_bytes(&mut self) -> Result<&'a [u8], &'static str> {
+        let len = self.get_u32()? as usize;
+        self.take(len)
+    }
+
+    pub fn get_string(&mut self) -> Result<String, &'static str> {
+        let bytes = self.get_bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "Invalid UTF-8 string")
+    }
+
+    pub fn get_option_string(&mut self) -> Result<Option<String>, &'static str> {
+        match self.get_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.get_string()?)),
+            _ => Err("Invalid option tag"),
+        }
+    }
+
+    pub fn get_list<T: Decode>(&mut self) -> Result<Vec<T>, &'static str> {
+        let len = self.get_u32()? as usize;
+        // 不信任长度前缀做预分配，防止恶意数据耗尽内存
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(T::decode(self)?);
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utxo::{OutPoint, TxOut};
+    use crate::{Block, BlockHeader, Transaction};
+    use ed25519_dalek::SigningKey;
+
+    fn signed_transaction() -> Transaction {
+        let key = SigningKey::from_bytes(&[3u8; 32]);
+        let mut tx = Transaction {
+            sender: hex::encode(key.verifying_key().to_bytes()),
+            receiver: String::new(),
+            amount: 0,
+            fee: 5,
+            nonce: 2,
+            inputs: vec![OutPoint { txid: "ab".repeat(32), vout: 1 }],
+            outputs: vec![
+                TxOut { address: "alice".to_string(), amount: 70 },
+                TxOut { address: "bob".to_string(), amount: 25 },
+            ],
+            signature: None,
+        };
+        tx.sign(&key);
+        tx
+    }
+
+    fn sample_block() -> Block {
+        let transactions = vec![Transaction::coinbase("miner", 50, 7), signed_transaction()];
+        Block::new(7, 0x207f_ffff, transactions, "cd".repeat(32))
+    }
+
+    #[test]
+    fn transaction_round_trip() {
+        let tx = signed_transaction();
+        let bytes = tx.to_bytes();
+        let decoded = Transaction::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(decoded.hash(), tx.hash());
+        assert_eq!(decoded.signature, tx.signature);
+        assert_eq!(decoded.inputs, tx.inputs);
+        assert_eq!(decoded.outputs, tx.outputs);
+        assert!(decoded.is_valid());
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let header = sample_block().header;
+        let bytes = header.to_bytes();
+        let decoded = BlockHeader::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(decoded.calculate_hash(), header.calculate_hash());
+    }
+
+    #[test]
+    fn block_round_trip() {
+        let block = sample_block();
+        let bytes = block.to_bytes();
+        let decoded = Block::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(decoded.hash, block.hash);
+        assert_eq!(decoded.transactions.len(), 2);
+        assert!(decoded.check_commitments().is_ok());
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let mut bytes = sample_block().to_bytes();
+        bytes.push(0);
+        assert_eq!(Block::from_bytes(&bytes).err(), Some("Trailing bytes after value"));
+    }
+
+    #[test]
+    fn bad_option_tag_is_rejected() {
+        // 没有签名的交易以 Option 标记结尾
+        let mut bytes = Transaction::coinbase("miner", 50, 0).to_bytes();
+        assert_eq!(bytes.last(), Some(&0));
+        *bytes.last_mut().unwrap() = 2;
+        assert_eq!(Transaction::from_bytes(&bytes).err(), Some("Invalid option tag"));
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        let bytes = signed_transaction().to_bytes();
+        assert!(Transaction::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,355 @@
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::difficulty;
+use crate::mempool::SavedTransaction;
+use crate::{Block, BlockHeader, Blockchain, Transaction};
+
+// 新区块接入后的结果
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BlockStatus {
+    // 直接接在主链链尾
+    Extended,
+    // 进入侧链，主链不变
+    SideChain,
+    // 侧链的累计工作量超过主链，发生了重组
+    Reorganized { disconnected: usize, connected: usize },
+}
+
+impl Blockchain {
+    // 校验并接入一个新区块，工作量更大的侧链会触发重组
+    pub fn submit_block(&mut self, block: Block) -> Result<BlockStatus, String> {
+        self.accept_block(block, true)
+    }
+
+    // persist 为 false 时用于启动时重放已保存的区块
+    pub(crate) fn accept_block(&mut self, block: Block, persist: bool) -> Result<BlockStatus, String> {
+        if self.contains_block(&block.hash) {
+            return Err("Block already known".to_string());
+        }
+        if block.header.previous_hash == self.chain.last().unwrap().hash {
+            self.extend_tip(block, persist)?;
+            return Ok(BlockStatus::Extended);
+        }
+
+        let (fork_height, mut path) = self
+            .branch_path(&block.header.previous_hash)
+            .ok_or("Unknown parent block")?;
+        // 难度与中位时间只需要区块头，不复制主链区块
+        let headers: Vec<&BlockHeader> = self.chain[..=fork_height]
+            .iter()
+            .chain(&path)
+            .map(|b| &b.header)
+            .collect();
+        let previous = path.last().unwrap_or(&self.chain[fork_height]);
+
+        // 侧链区块先只做不依赖账本的检查，完整校验在重组时进行
+        block
+            .validate(
+                Some(previous),
+                self.difficulty_rules.next_bits(&headers),
+                difficulty::median_time_past(&headers),
+            )
+            .map_err(|e| e.to_string())?;
+        if persist && self.store.append_block(&block).is_err() {
+            return Err("Failed to persist block".to_string());
+        }
+
+        let candidate_work = path
+            .iter()
+            .chain(std::iter::once(&block))
+            .fold(self.chain_work[fork_height], |work, b| {
+                work.saturating_add(&b.header.work())
+            });
+        self.side_blocks.insert(block.hash.clone(), block.clone());
+        path.push(block);
+
+        if candidate_work <= self.total_work() {
+            return Ok(BlockStatus::SideChain);
+        }
+        self.reorganize(path, fork_height)
+    }
+
+    pub fn contains_block(&self, hash: &str) -> bool {
+        self.side_blocks.contains_key(hash) || self.height_of(hash).is_some()
+    }
+
+    // 在主链和侧链中查找区块
+    pub fn get_block(&self, hash: &str) -> Option<&Block> {
+        match self.height_of(hash) {
+            Some(height) => Some(&self.chain[height]),
+            None => self.side_blocks.get(hash),
+        }
+    }
+
+    // 主链上哈希为 hash 的区块高度
+    pub fn height_of(&self, hash: &str) -> Option<usize> {
+        self.heights.get(hash).copied()
+    }
+
+    // 包含交易 ID 为 txid 的交易的主链区块高度
+    pub fn tx_height(&self, txid: &str) -> Option<usize> {
+        self.tx_heights.get(txid).copied()
+    }
+
+    // 把区块接到主链链尾
+    fn extend_tip(&mut self, block: Block, persist: bool) -> Result<(), String> {
+        self.connect_tip(block, persist)?;
+        if persist {
+            self.prune_mempool();
+        }
+        Ok(())
+    }
+
+    // 完整校验区块后把它接到链尾，同时更新账本、UTXO 集合和索引
+    fn connect_tip(&mut self, block: Block, persist: bool) -> Result<(), String> {
+        block
+            .validate(self.chain.last(), self.next_bits(), difficulty::median_time_past(&self.chain))
+            .map_err(|e| e.to_string())?;
+        let mut ledger = self.ledger.clone();
+        block
+            .validate_coinbase(self.block_reward(
+                block.header.index,
+                ledger.issued(),
+                block.total_fees(),
+            ))
+            .map_err(|e| e.to_string())?;
+        ledger.apply_block(&block).map_err(|e| e.to_string())?;
+        self.check_block_model(&block).map_err(|e| e.to_string())?;
+        if let Some(utxo) = &mut self.utxo {
+            utxo.connect_block(&block).map_err(|e| e.to_string())?;
+        }
+
+        // 先落盘再修改链，写入失败时撤销 UTXO 集合的修改
+        if persist && self.store.append_block(&block).is_err() {
+            if let Some(utxo) = &mut self.utxo {
+                utxo.disconnect_block(&block);
+            }
+            return Err("Failed to persist block".to_string());
+        }
+        self.push_block(block);
+        self.ledger = ledger;
+        Ok(())
+    }
+
+    // 断开链尾区块，账本和 UTXO 集合退回到它的父区块
+    fn disconnect_tip(&mut self) -> Block {
+        let block = self.pop_block();
+        self.ledger.revert_block(&block);
+        if let Some(utxo) = &mut self.utxo {
+            utxo.disconnect_block(&block);
+        }
+        block
+    }
+
+    // 重新接入刚刚断开的区块，它们在断开前已经通过校验
+    fn reconnect_tip(&mut self, block: Block) {
+        self.ledger.apply_block(&block).expect("block was connected before");
+        if let Some(utxo) = &mut self.utxo {
+            utxo.connect_block(&block).expect("block was connected before");
+        }
+        self.push_block(block);
+    }
+
+    // 把区块放到主链链尾并加入索引
+    fn push_block(&mut self, block: Block) {
+        let height = self.chain.len();
+        self.chain_work.push(self.total_work().saturating_add(&block.header.work()));
+        self.heights.insert(block.hash.clone(), height);
+        for tx in &block.transactions {
+            self.tx_heights.insert(tx.hash(), height);
+        }
+        self.chain.push(block);
+    }
+
+    // 取下主链链尾的区块并移出索引
+    fn pop_block(&mut self) -> Block {
+        let block = self.chain.pop().expect("genesis block is never disconnected");
+        self.chain_work.pop();
+        self.heights.remove(&block.hash);
+        for tx in &block.transactions {
+            self.tx_heights.remove(&tx.hash());
+        }
+        block
+    }
+
+    // 从 hash 沿侧链回溯到主链，返回分叉点高度和分叉点之后的侧链区块（按高度升序）
+    fn branch_path(&self, hash: &str) -> Option<(usize, Vec<Block>)> {
+        let mut path = Vec::new();
+        let mut current = hash;
+        loop {
+            if let Some(height) = self.height_of(current) {
+                path.reverse();
+                return Some((height, path));
+            }
+            let block = self.side_blocks.get(current)?;
+            path.push(block.clone());
+            current = &block.header.previous_hash;
+        }
+    }
+
+    // 断开分叉点之后的主链区块，换成 path 中的侧链区块（按高度升序）
+    // 只校验分叉点之后的区块，失败时恢复原来的主链
+    fn reorganize(&mut self, path: Vec<Block>, fork_height: usize) -> Result<BlockStatus, String> {
+        let mut disconnected = Vec::new();
+        while self.chain.len() > fork_height + 1 {
+            disconnected.push(self.disconnect_tip());
+        }
+        disconnected.reverse();
+
+        for (i, block) in path.iter().enumerate() {
+            if let Err(e) = self.connect_tip(block.clone(), false) {
+                // 出错的区块及其之后的侧链区块都不合法
+                for block in &path[i..] {
+                    self.side_blocks.remove(&block.hash);
+                }
+                while self.chain.len() > fork_height + 1 {
+                    self.disconnect_tip();
+                }
+                for block in disconnected {
+                    self.reconnect_tip(block);
+                }
+                return Err(format!("block {}: {}", fork_height + 1 + i, e));
+            }
+        }
+        let connected = path.len();
+        for block in &path {
+            self.side_blocks.remove(&block.hash);
+        }
+        println!(
+            "Reorganized at height {}: {} blocks disconnected, {} connected",
+            fork_height,
+            disconnected.len(),
+            connected
+        );
+
+        // 断开区块中的交易（coinbase 除外）放回内存池
+        let returned: Vec<Transaction> = disconnected
+            .iter()
+            .flat_map(|block| block.transactions.iter().filter(|tx| !tx.is_coinbase()))
+            .cloned()
+            .collect();
+        let status = BlockStatus::Reorganized {
+            disconnected: disconnected.len(),
+            connected,
+        };
+        for block in disconnected {
+            self.side_blocks.insert(block.hash.clone(), block);
+        }
+        let now = Utc::now().timestamp();
+        let returned = returned
+            .into_iter()
+            .map(|tx| SavedTransaction { tx, added_at: now })
+            .collect();
+        self.mempool.restore(returned, self.ledger.clone(), self.utxo.as_ref());
+        self.prune_mempool();
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    // 在 parent 之上构造并挖出一个只含 coinbase 的区块，coinbase 领取 reward
+    fn child_block(bc: &Blockchain, parent: &Block, miner: &str, reward: u64) -> Block {
+        let height = parent.header.index + 1;
+        let coinbase = Transaction::coinbase(miner, reward, height);
+        let mut block = Block::new(height, parent.header.bits, vec![coinbase], parent.hash.clone());
+        block.header.timestamp = parent.header.timestamp + 1;
+        block.mine(1);
+        assert!(!bc.contains_block(&block.hash));
+        block
+    }
+
+    // 从 parent 开始依次提交 count 个侧链区块，返回提交的区块和最后一次的结果
+    fn submit_branch(bc: &mut Blockchain, parent: &Block, count: usize) -> (Vec<Block>, Result<BlockStatus, String>) {
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut status = Err("no blocks".to_string());
+        for _ in 0..count {
+            let parent = blocks.last().unwrap_or(parent).clone();
+            let reward = bc.policy.subsidy_at(parent.header.index + 1);
+            let block = child_block(bc, &parent, "side", reward);
+            status = bc.submit_block(block.clone());
+            blocks.push(block);
+        }
+        (blocks, status)
+    }
+
+    #[test]
+    fn heavier_side_branch_takes_over() {
+        let mut bc = Blockchain::new(1);
+        bc.mine_pending_transactions("main").unwrap();
+        bc.mine_pending_transactions("main").unwrap();
+        let genesis = bc.chain[0].clone();
+
+        let (side, status) = submit_branch(&mut bc, &genesis, 2);
+        assert_eq!(status, Ok(BlockStatus::SideChain));
+        assert_eq!(bc.tip().hash, bc.chain[2].hash);
+        assert_ne!(bc.tip().hash, side[1].hash);
+
+        let third = child_block(&bc, &side[1], "side", bc.policy.subsidy_at(3));
+        let status = bc.submit_block(third.clone());
+        assert_eq!(status, Ok(BlockStatus::Reorganized { disconnected: 2, connected: 3 }));
+        assert_eq!(bc.tip().hash, third.hash);
+        assert_eq!(bc.ledger().balance("main"), 0);
+        assert_eq!(bc.ledger().balance("side"), 3 * bc.policy.subsidy_at(1));
+        assert!(bc.validate_chain().is_ok());
+    }
+
+    #[test]
+    fn disconnected_transactions_return_to_mempool() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let sender = hex::encode(key.verifying_key().to_bytes());
+        let mut bc = Blockchain::new(1);
+        bc.mine_pending_transactions(&sender).unwrap();
+        let fork_point = bc.tip().clone();
+
+        let mut tx = Transaction {
+            sender: sender.clone(),
+            receiver: "bob".to_string(),
+            amount: 10,
+            fee: 1,
+            nonce: 0,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            signature: None,
+        };
+        tx.sign(&key);
+        bc.add_transaction(tx.clone()).unwrap();
+        bc.mine_pending_transactions("main").unwrap();
+        assert!(bc.mempool.is_empty());
+        assert_eq!(bc.ledger().balance("bob"), 10);
+
+        let (_, status) = submit_branch(&mut bc, &fork_point, 2);
+        assert_eq!(status, Ok(BlockStatus::Reorganized { disconnected: 1, connected: 2 }));
+        assert!(bc.mempool.contains(&tx.hash()));
+        assert_eq!(bc.ledger().balance("bob"), 0);
+        assert_eq!(bc.ledger().next_nonce(&sender), 0);
+    }
+
+    #[test]
+    fn invalid_side_branch_is_dropped() {
+        let mut bc = Blockchain::new(1);
+        bc.mine_pending_transactions("main").unwrap();
+        bc.mine_pending_transactions("main").unwrap();
+        let main_tip = bc.tip().hash.clone();
+        let genesis = bc.chain[0].clone();
+
+        let (side, _) = submit_branch(&mut bc, &genesis, 1);
+        // coinbase 领取的金额超过区块奖励，只有完整校验才能发现
+        let bad = child_block(&bc, &side[0], "side", bc.policy.subsidy_at(2) + 1);
+        assert_eq!(bc.submit_block(bad.clone()), Ok(BlockStatus::SideChain));
+        let last = child_block(&bc, &bad, "side", bc.policy.subsidy_at(3));
+        assert!(bc.submit_block(last.clone()).is_err());
+
+        assert_eq!(bc.tip().hash, main_tip);
+        assert!(bc.contains_block(&side[0].hash));
+        assert!(!bc.contains_block(&bad.hash));
+        assert!(!bc.contains_block(&last.hash));
+        assert_eq!(bc.ledger().balance("main"), 2 * bc.policy.subsidy_at(1));
+        assert!(bc.validate_chain().is_ok());
+    }
+}
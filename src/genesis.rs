@@ -0,0 +1,79 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::amount::{self, COIN};
+use crate::miner::CancelToken;
+use crate::target::U256;
+use crate::{Block, Transaction};
+
+// 创世区块的固定参数，同一网络的所有节点必须使用相同的配置
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GenesisConfig {
+    pub timestamp: i64,
+    // 省略时用单线程从 0 开始搜索，每个节点都会得到相同的结果
+    #[serde(default)]
+    pub nonce: Option<u64>,
+    pub recipient: String,
+    // 创世区块的奖励是配置的一部分，不受 BLOCK_SUBSIDY 等发行规则覆盖的影响
+    #[serde(with = "amount::decimal", default = "default_reward")]
+    pub reward: u64,
+    pub bits: u32, // 创世区块的目标值，也是区块链的初始目标值
+}
+
+fn default_reward() -> u64 {
+    50 * COIN
+}
+
+impl Default for GenesisConfig {
+    fn default() -> Self {
+        GenesisConfig {
+            timestamp: 1_704_067_200, // 2024-01-01 00:00:00 UTC
+            nonce: Some(5285),
+            recipient: "Creator".to_string(),
+            reward: default_reward(),
+            bits: 0x1f00_ffff,
+        }
+    }
+}
+
+impl GenesisConfig {
+    // 从 JSON 文件读取，例如
+    // {"timestamp": 1704067200, "nonce": 12345, "recipient": "Creator", "reward": "50", "bits": 520159231}
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        serde_json::from_slice(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // 目标值必须是合法的非零值，且不低于网络允许的最低难度 pow_limit
+    pub fn validate(&self, pow_limit: U256) -> io::Result<()> {
+        match U256::from_compact(self.bits) {
+            Some(target) if !target.is_zero() && target <= pow_limit => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Genesis bits {:#010x} are not a valid target", self.bits),
+            )),
+        }
+    }
+
+    // 按配置构造创世区块，结果只取决于配置本身
+    pub fn build(&self) -> io::Result<Block> {
+        let transactions = vec![Transaction::coinbase(&self.recipient, self.reward, 0)];
+        let mut block = Block::new(0, self.bits, transactions, "0".to_string());
+        block.header.timestamp = self.timestamp;
+        match self.nonce {
+            Some(nonce) => {
+                block.header.nonce = nonce;
+                block.hash = block.header.calculate_hash();
+            }
+            None => {
+                block
+                    .mine_cancellable(1, &CancelToken::default())
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Genesis bits are not a valid target"))?;
+            }
+        }
+        Ok(block)
+    }
+}
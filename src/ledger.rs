@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use crate::{Block, BlockError, Transaction};
+
+// 由链上交易（以及可选的内存池交易）推导出的账户余额与交易序号
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    balances: HashMap<String, u64>,
+    nonces: HashMap<String, u64>,
+    // 流通总量: coinbase 累计发行量减去已支付、尚未被 coinbase 领取的手续费
+    issued: u64,
+}
+
+impl Ledger {
+    pub fn from_chain(chain: &[Block]) -> Self {
+        let mut ledger = Ledger::default();
+        for block in chain {
+            for tx in &block.transactions {
+                // 已上链的交易在区块校验时检查，这里只负责记账
+                let _ = ledger.apply(tx);
+            }
+        }
+        ledger
+    }
+
+    pub fn balance(&self, address: &str) -> u64 {
+        self.balances.get(address).copied().unwrap_or(0)
+    }
+
+    // 依次记入区块中的所有交易，遇到第一笔不合法的交易时返回错误
+    pub fn apply_block(&mut self, block: &Block) -> Result<(), BlockError> {
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            self.apply(tx)
+                .map_err(|detail| BlockError::BadTransfer { tx_index, detail })?;
+        }
+        Ok(())
+    }
+
+    pub fn issued(&self) -> u64 {
+        self.issued
+    }
+
+    // 所有账户余额之和
+    pub fn total_balance(&self) -> u64 {
+        self.balances.values().fold(0, |sum, b| sum.saturating_add(*b))
+    }
+
+    // 发送者下一笔交易应使用的 nonce
+    pub fn next_nonce(&self, address: &str) -> u64 {
+        self.nonces.get(address).copied().unwrap_or(0)
+    }
+
+    // 检查交易金额、序号与发送者余额，通过后记入账本
+    // UTXO 交易的输入由 UtxoSet 检查，这里按输出总额加手续费扣款
+    pub fn apply(&mut self, tx: &Transaction) -> Result<(), &'static str> {
+        if tx.is_utxo() {
+            if tx.is_coinbase() {
+                return Err("Coinbase cannot have inputs or outputs");
+            }
+            if !tx.receiver.is_empty() || tx.amount != 0 {
+                return Err("UTXO transaction must not set receiver or amount");
+            }
+        }
+        let outputs = tx.outputs();
+        // 发行量耗尽后 coinbase 金额可以为 0
+        if !tx.is_coinbase() && (outputs.is_empty() || outputs.iter().any(|out| out.amount == 0)) {
+            return Err("Invalid transaction amount");
+        }
+        if !tx.is_coinbase() {
+            let expected = self.next_nonce(&tx.sender);
+            if tx.nonce < expected {
+                return Err("Stale nonce: already used by a previous transaction");
+            }
+            if tx.nonce > expected {
+                return Err("Nonce too high: earlier transactions are missing");
+            }
+        }
+        if tx.is_coinbase() && tx.fee != 0 {
+            return Err("Coinbase cannot pay a fee");
+        }
+        let total = outputs
+            .iter()
+            .try_fold(tx.fee, |sum, out| sum.checked_add(out.amount))
+            .ok_or("Amount plus fee overflows")?;
+
+        // 新余额先记在 updates 中，转给自己或多个输出指向同一地址时基于已更新的余额计算
+        let mut updates: HashMap<&str, u64> = HashMap::new();
+        // System 交易凭空发行货币，不需要余额；其他交易还要扣除手续费
+        if !tx.is_coinbase() {
+            let balance = self
+                .balance(&tx.sender)
+                .checked_sub(total)
+                .ok_or("Insufficient balance")?;
+            updates.insert(&tx.sender, balance);
+        }
+        for out in &outputs {
+            let before = match updates.get(out.address.as_str()) {
+                Some(balance) => *balance,
+                None => self.balance(&out.address),
+            };
+            let after = before
+                .checked_add(out.amount)
+                .ok_or("Receiver balance overflows")?;
+            updates.insert(&out.address, after);
+        }
+        let issued = if tx.is_coinbase() {
+            self.issued.checked_add(total).ok_or("Total supply overflows")?
+        } else {
+            // 手续费离开发送者账户，之后由 coinbase 重新发给矿工
+            self.issued.saturating_sub(tx.fee)
+        };
+
+        // 先完成所有检查再写入，失败时账本保持不变
+        for (address, balance) in updates {
+            self.balances.insert(address.to_string(), balance);
+        }
+        if !tx.is_coinbase() {
+            self.nonces.insert(tx.sender.clone(), tx.nonce + 1);
+        }
+        self.issued = issued;
+        Ok(())
+    }
+
+    // 撤销最后记入的区块，用于重组时把账本退回到分叉点
+    pub fn revert_block(&mut self, block: &Block) {
+        for tx in block.transactions.iter().rev() {
+            self.revert(tx);
+        }
+    }
+
+    // apply 的逆操作，tx 必须是最后一笔成功记入的交易
+    fn revert(&mut self, tx: &Transaction) {
+        let outputs = tx.outputs();
+        let total = outputs.iter().fold(tx.fee, |sum, out| sum.saturating_add(out.amount));
+        for out in &outputs {
+            let balance = self.balance(&out.address).saturating_sub(out.amount);
+            self.balances.insert(out.address.clone(), balance);
+        }
+        if tx.is_coinbase() {
+            self.issued = self.issued.saturating_sub(total);
+        } else {
+            let balance = self.balance(&tx.sender).saturating_add(total);
+            self.balances.insert(tx.sender.clone(), balance);
+            self.nonces.insert(tx.sender.clone(), tx.nonce);
+            self.issued = self.issued.saturating_add(tx.fee);
+        }
+    }
+}
@@ -1,49 +1,104 @@
+mod amount;
+mod difficulty;
+mod encoding;
+mod fork;
+mod genesis;
+mod ledger;
+mod mempool;
+mod merkle;
+mod miner;
+mod network;
+mod p2p;
+mod storage;
+mod supply;
+mod sync;
+mod target;
+mod utxo;
+
 use sha2::{Sha256, Digest};
 use chrono::prelude::*;
 use serde::{Serialize, Deserialize};
 use ed25519_dalek::{SigningKey, Signature, Signer, Verifier, VerifyingKey};
 use axum::{
     routing::{get, post},
-    Json, Router, extract::State,
+    Json, Router, extract::{Path, Query, State},
     http::StatusCode,
 };
-use std::sync::{Arc, RwLock};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex, RwLock};
 use tower_http::cors::CorsLayer;
+use difficulty::DifficultyRules;
+use genesis::GenesisConfig;
+use encoding::{Decode, Encode, Reader, Writer};
+use fork::BlockStatus;
+use ledger::Ledger;
+use mempool::{Mempool, MempoolConfig};
+use merkle::MerkleProof;
+use miner::{CancelToken, MiningResult};
+use network::{Network, NetworkParams};
+use storage::{BlockStore, FileStore, MemoryStore};
+use supply::{MonetaryPolicy, SupplyReport};
+use target::U256;
+use utxo::{OutPoint, TxOut, UtxoSet, UtxoView};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Transaction {
     pub sender: String,    // 发送者的公钥 (Hex 字符串)
     pub receiver: String,  // 接收者的地址或公钥
-    pub amount: f64,
+    #[serde(with = "amount::decimal")]
+    pub amount: u64, // 以最小单位计
+    #[serde(with = "amount::decimal", default)]
+    pub fee: u64,          // 支付给矿工的手续费
+    pub nonce: u64,        // 发送者的交易序号，防止重放
+    // UTXO 模型: 花费的输出和新创建的输出，此时 receiver 为空、amount 为 0
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<OutPoint>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<TxOut>,
     pub signature: Option<String>, // 签名 (Hex 字符串)
 }
 
 impl Transaction {
-    // 计算交易的哈希，用于签名
-    pub fn calculate_hash(&self) -> Vec<u8> {
-        let data = format!("{}{}{}", self.sender, self.receiver, self.amount);
+    // 参与签名的字段（不含签名本身）的规范编码
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.put_str(&self.sender);
+        w.put_str(&self.receiver);
+        w.put_u64(self.amount);
+        w.put_u64(self.fee);
+        w.put_u64(self.nonce);
+        w.put_list(&self.inputs);
+        w.put_list(&self.outputs);
+        w.into_bytes()
+    }
+
+    // 签名哈希: 只覆盖参与签名的字段，签名的就是这个哈希
+    pub fn sighash(&self) -> Vec<u8> {
         let mut hasher = Sha256::new();
-        hasher.update(data.as_bytes());
+        hasher.update(self.signing_bytes());
         hasher.finalize().to_vec()
     }
 
     // 使用私钥对交易进行签名
     pub fn sign(&mut self, signing_key: &SigningKey) {
-        let message = self.calculate_hash();
+        let message = self.sighash();
         let signature = signing_key.sign(&message);
         self.signature = Some(hex::encode(signature.to_bytes()));
     }
 
     // 验证交易签名是否合法
     pub fn is_valid(&self) -> bool {
-        // 创世区块的系统交易跳过验证
-        if self.sender == "System" {
-            return true;
+        // coinbase 交易没有签名，由区块校验限制其位置与金额
+        if self.is_coinbase() {
+            return self.signature.is_none();
         }
 
+        // 签名参与交易 ID 的计算，只接受唯一的编码形式，防止改写签名得到不同的 txid
         let sig_hex = match &self.signature {
-            Some(s) => s,
-            None => return false,
+            Some(s) if Transaction::is_canonical_signature(s) => s,
+            _ => return false,
         };
 
         // 1. 解析公钥
@@ -71,9 +126,110 @@ impl Transaction {
         };
 
         // 3. 验证
-        let message = self.calculate_hash();
+        let message = self.sighash();
         verifying_key.verify(&message, &signature).is_ok()
     }
+
+    // 规范的签名编码: 64 字节签名的小写十六进制，共 128 个字符
+    pub fn is_canonical_signature(sig_hex: &str) -> bool {
+        sig_hex.len() == 2 * ed25519_dalek::SIGNATURE_LENGTH
+            && sig_hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    }
+}
+
+impl Transaction {
+    // 区块的第一笔交易，由 System 向矿工发行奖励
+    // nonce 取区块高度，保证不同区块的 coinbase 哈希不同
+    pub fn coinbase(receiver: &str, amount: u64, height: u32) -> Self {
+        Transaction {
+            sender: "System".to_string(),
+            receiver: receiver.to_string(),
+            amount,
+            fee: 0,
+            nonce: height as u64,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            signature: None,
+        }
+    }
+
+    // 交易 ID: 完整规范编码（包括签名）的哈希，Merkle 树和 UTXO 引用都使用它
+    pub fn txid(&self) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.to_bytes());
+        hasher.finalize().to_vec()
+    }
+
+    // 十六进制的交易 ID
+    pub fn hash(&self) -> String {
+        hex::encode(self.txid())
+    }
+
+    // 见证数据（签名）的哈希，用于区块头中的见证承诺
+    pub fn witness_hash(&self) -> String {
+        let mut w = Writer::default();
+        w.put_option_str(self.signature.as_deref());
+        let mut hasher = Sha256::new();
+        hasher.update(w.into_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    // 交易创建的输出: UTXO 交易为显式的 outputs，其他交易只有一个向 receiver 支付 amount 的输出
+    pub fn outputs(&self) -> Vec<TxOut> {
+        if self.outputs.is_empty() && self.inputs.is_empty() {
+            vec![TxOut {
+                address: self.receiver.clone(),
+                amount: self.amount,
+            }]
+        } else {
+            self.outputs.clone()
+        }
+    }
+
+    pub fn is_utxo(&self) -> bool {
+        !self.inputs.is_empty() || !self.outputs.is_empty()
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.sender == "System"
+    }
+
+    // 规范编码后的字节数，用于计算手续费率
+    pub fn size(&self) -> usize {
+        self.to_bytes().len()
+    }
+}
+
+impl Encode for Transaction {
+    fn encode(&self, w: &mut Writer) {
+        w.put_str(&self.sender);
+        w.put_str(&self.receiver);
+        w.put_u64(self.amount);
+        w.put_u64(self.fee);
+        w.put_u64(self.nonce);
+        w.put_list(&self.inputs);
+        w.put_list(&self.outputs);
+        w.put_option_str(self.signature.as_deref());
+    }
+}
+
+impl Decode for Transaction {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str> {
+        let tx = Transaction {
+            sender: r.get_string()?,
+            receiver: r.get_string()?,
+            amount: r.get_u64()?,
+            fee: r.get_u64()?,
+            nonce: r.get_u64()?,
+            inputs: r.get_list()?,
+            outputs: r.get_list()?,
+            signature: r.get_option_string()?,
+        };
+        if tx.signature.as_deref().is_some_and(|sig| !Transaction::is_canonical_signature(sig)) {
+            return Err("non-canonical signature encoding");
+        }
+        Ok(tx)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -81,20 +237,120 @@ pub struct BlockHeader {
     pub index: u32,
     pub timestamp: i64,
     pub merkle_root: String,
+    pub witness_root: String, // 所有交易签名的 Merkle 根
     pub previous_hash: String,
+    pub bits: u32, // 紧凑格式的工作量证明目标值
     pub nonce: u64,
 }
 
 impl BlockHeader {
     pub fn calculate_hash(&self) -> String {
-        let data = format!(
-            "{}{}{}{}{}",
-            self.index, self.timestamp, self.merkle_root, self.previous_hash, self.nonce
-        );
         let mut hasher = Sha256::new();
-        hasher.update(data.as_bytes());
+        hasher.update(self.to_bytes());
         format!("{:x}", hasher.finalize())
     }
+
+    // 解码 bits 得到的目标值，非法或为 0 时返回 None
+    pub fn target(&self) -> Option<U256> {
+        U256::from_compact(self.bits).filter(|target| !target.is_zero())
+    }
+
+    // 该区块贡献的工作量
+    pub fn work(&self) -> U256 {
+        self.target().map_or(U256::ZERO, |target| target.work())
+    }
+
+    // 只依赖区块头的检查: 高度、与前一个区块头的链接、目标值和工作量证明
+    pub fn validate(
+        &self,
+        previous: Option<&BlockHeader>,
+        expected_bits: u32,
+        median_time_past: i64,
+    ) -> Result<(), BlockError> {
+        let expected_index = previous.map_or(0, |prev| prev.index + 1);
+        if self.index != expected_index {
+            return Err(BlockError::BadIndex {
+                expected: expected_index,
+                found: self.index,
+            });
+        }
+
+        let expected_previous = previous.map_or_else(|| "0".to_string(), |prev| prev.calculate_hash());
+        if self.previous_hash != expected_previous {
+            return Err(BlockError::BadLink {
+                expected: expected_previous,
+                found: self.previous_hash.clone(),
+            });
+        }
+
+        if self.bits != expected_bits {
+            return Err(BlockError::BadBits {
+                expected: expected_bits,
+                found: self.bits,
+            });
+        }
+
+        // 时间戳必须晚于前 11 个区块的中位时间，且不能超前本地时间太多
+        if self.timestamp <= median_time_past {
+            return Err(BlockError::TimeTooOld {
+                median_time_past,
+                found: self.timestamp,
+            });
+        }
+        let max_timestamp = Utc::now().timestamp() + difficulty::MAX_FUTURE_BLOCK_SECS;
+        if self.timestamp > max_timestamp {
+            return Err(BlockError::TimeTooNew {
+                max_timestamp,
+                found: self.timestamp,
+            });
+        }
+
+        if !self.meets_target() {
+            return Err(BlockError::BadProofOfWork);
+        }
+        Ok(())
+    }
+
+    // 哈希作为整数不超过区块头自身的目标值
+    pub fn meets_target(&self) -> bool {
+        match (self.target(), U256::from_hex(&self.calculate_hash())) {
+            (Some(target), Some(value)) => value <= target,
+            _ => false,
+        }
+    }
+}
+
+impl AsRef<BlockHeader> for BlockHeader {
+    fn as_ref(&self) -> &BlockHeader {
+        self
+    }
+}
+
+impl Encode for BlockHeader {
+    fn encode(&self, w: &mut Writer) {
+        w.put_u32(self.index);
+        w.put_i64(self.timestamp);
+        w.put_str(&self.merkle_root);
+        w.put_str(&self.witness_root);
+        w.put_str(&self.previous_hash);
+        w.put_u32(self.bits);
+        // nonce 必须是最后一个字段，挖矿时只重新哈希这 8 个字节
+        w.put_u64(self.nonce);
+    }
+}
+
+impl Decode for BlockHeader {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str> {
+        Ok(BlockHeader {
+            index: r.get_u32()?,
+            timestamp: r.get_i64()?,
+            merkle_root: r.get_string()?,
+            witness_root: r.get_string()?,
+            previous_hash: r.get_string()?,
+            bits: r.get_u32()?,
+            nonce: r.get_u64()?,
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -105,15 +361,18 @@ pub struct Block {
 }
 
 impl Block {
-    pub fn new(index: u32, transactions: Vec<Transaction>, previous_hash: String) -> Self {
+    pub fn new(index: u32, bits: u32, transactions: Vec<Transaction>, previous_hash: String) -> Self {
         let timestamp = Utc::now().timestamp();
         let merkle_root = Block::calculate_merkle_root(&transactions);
+        let witness_root = Block::calculate_witness_root(&transactions);
         
         let header = BlockHeader {
             index,
             timestamp,
             merkle_root,
+            witness_root,
             previous_hash,
+            bits,
             nonce: 0,
         };
         
@@ -126,141 +385,1072 @@ impl Block {
         }
     }
 
-    // A real Merkle Root calculation (Simplified for now)
+    // 交易 ID 的 Merkle 根，构造规则见 merkle 模块
     fn calculate_merkle_root(transactions: &[Transaction]) -> String {
-        let mut hashes: Vec<String> = transactions
+        merkle::merkle_root(transactions.iter().map(Transaction::hash).collect())
+    }
+
+    // 见证承诺: 替换任何一笔交易的签名都会改变区块哈希
+    fn calculate_witness_root(transactions: &[Transaction]) -> String {
+        merkle::merkle_root(transactions.iter().map(Transaction::witness_hash).collect())
+    }
+
+    // 证明哈希为 tx_hash 的交易包含在本区块中
+    pub fn merkle_proof(&self, tx_hash: &str) -> Option<MerkleProof> {
+        let leaves: Vec<String> = self.transactions.iter().map(Transaction::hash).collect();
+        let index = leaves.iter().position(|hash| hash == tx_hash)?;
+        Some(MerkleProof {
+            tx_hash: tx_hash.to_string(),
+            index,
+            tx_count: leaves.len(),
+            branch: merkle::merkle_branch(&leaves, index)?,
+            header: self.header.clone(),
+        })
+    }
+
+    // 使用 threads 个线程并行搜索满足 header.bits 的 nonce
+    pub fn mine(&mut self, threads: usize) -> MiningResult {
+        self.mine_cancellable(threads, &CancelToken::default())
+            .expect("mining a valid target without a cancel token always finishes")
+    }
+
+    // 可被取消的挖矿；被取消或目标值非法时区块保持不变并返回 None
+    pub fn mine_cancellable(&mut self, threads: usize, cancel: &CancelToken) -> Option<MiningResult> {
+        let target = self.header.target()?;
+        let result = miner::search_nonce(&self.header, target, threads, cancel)?;
+        self.header.nonce = result.nonce;
+        self.hash = result.hash.clone();
+        println!(
+            "Block Mined! Hash: {} ({} threads, {:.0} H/s)",
+            self.hash, result.threads, result.hashrate
+        );
+        Some(result)
+    }
+
+    // 区块内除 coinbase 外所有交易的手续费之和
+    pub fn total_fees(&self) -> u64 {
+        self.transactions
             .iter()
-            .map(|tx| hex::encode(tx.calculate_hash()))
-            .collect();
+            .filter(|tx| !tx.is_coinbase())
+            .fold(0, |sum, tx| sum.saturating_add(tx.fee))
+    }
 
-        if hashes.is_empty() {
-            return String::from("0");
+    // 每个区块恰好有一笔 coinbase 且位于第一位，金额不超过 max_reward
+    pub fn validate_coinbase(&self, max_reward: u64) -> Result<(), BlockError> {
+        let coinbase = match self.transactions.first() {
+            Some(tx) if tx.is_coinbase() => tx,
+            _ => return Err(BlockError::MissingCoinbase),
+        };
+        if let Some(pos) = self.transactions.iter().skip(1).position(|tx| tx.is_coinbase()) {
+            return Err(BlockError::MisplacedCoinbase { tx_index: pos + 1 });
+        }
+        // coinbase 的 nonce 必须是区块高度，保证不同区块的 coinbase 交易 ID 不同 (BIP34)
+        if coinbase.nonce != self.header.index as u64 {
+            return Err(BlockError::BadCoinbaseHeight {
+                expected: self.header.index,
+                found: coinbase.nonce,
+            });
         }
+        if coinbase.amount > max_reward {
+            return Err(BlockError::ExcessiveCoinbase {
+                allowed: max_reward,
+                found: coinbase.amount,
+            });
+        }
+        Ok(())
+    }
 
-        while hashes.len() > 1 {
-            if hashes.len() % 2 != 0 {
-                let last = hashes.last().unwrap().clone();
-                hashes.push(last);
-            }
+    // 不依赖链上下文的检查: 哈希与区块头一致，区块头中的 Merkle 根和见证根与交易一致
+    // 通过后区块内容就是区块头所承诺的内容
+    pub fn check_commitments(&self) -> Result<(), BlockError> {
+        // 存储的哈希必须与区块头重新计算的结果一致
+        if self.hash != self.header.calculate_hash() {
+            return Err(BlockError::BadHash);
+        }
+        if self.header.merkle_root != Block::calculate_merkle_root(&self.transactions) {
+            return Err(BlockError::BadMerkleRoot);
+        }
+        if self.header.witness_root != Block::calculate_witness_root(&self.transactions) {
+            return Err(BlockError::BadWitnessRoot);
+        }
+        Ok(())
+    }
 
-            let mut new_hashes = Vec::new();
-            for i in (0..hashes.len()).step_by(2) {
-                let mut hasher = Sha256::new();
-                hasher.update(format!("{}{}", hashes[i], hashes[i+1]).as_bytes());
-                new_hashes.push(format!("{:x}", hasher.finalize()));
-            }
-            hashes = new_hashes;
+    // 校验区块自身的完整性，以及与前一个区块的链接关系
+    // expected_bits 为按难度调整规则该区块应使用的目标值，median_time_past 为之前区块的中位时间
+    pub fn validate(
+        &self,
+        previous: Option<&Block>,
+        expected_bits: u32,
+        median_time_past: i64,
+    ) -> Result<(), BlockError> {
+        self.check_commitments()?;
+        self.header
+            .validate(previous.map(|prev| &prev.header), expected_bits, median_time_past)?;
+
+        // 同一笔交易不能在区块中出现两次
+        let mut seen = std::collections::HashSet::new();
+        if let Some(tx_index) = self.transactions.iter().position(|tx| !seen.insert(tx.hash())) {
+            return Err(BlockError::DuplicateTransaction { tx_index });
+        }
+
+        if let Some(tx_index) = self.transactions.iter().position(|tx| !tx.is_valid()) {
+            return Err(BlockError::BadSignature { tx_index });
         }
 
-        hashes[0].clone()
+        Ok(())
     }
+}
+
+impl Encode for Block {
+    fn encode(&self, w: &mut Writer) {
+        self.header.encode(w);
+        w.put_str(&self.hash);
+        w.put_list(&self.transactions);
+    }
+}
 
-    pub fn mine(&mut self, difficulty: usize) {
-        let target = "0".repeat(difficulty);
-        while &self.hash[..difficulty] != target {
-            self.header.nonce += 1;
-            self.hash = self.header.calculate_hash();
+impl Decode for Block {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str> {
+        Ok(Block {
+            header: BlockHeader::decode(r)?,
+            hash: r.get_string()?,
+            transactions: r.get_list()?,
+        })
+    }
+}
+
+impl AsRef<BlockHeader> for Block {
+    fn as_ref(&self) -> &BlockHeader {
+        &self.header
+    }
+}
+
+// 区块校验失败的原因
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum BlockError {
+    BadIndex { expected: u32, found: u32 },
+    BadLink { expected: String, found: String },
+    BadBits { expected: u32, found: u32 },
+    TimeTooOld { median_time_past: i64, found: i64 },
+    TimeTooNew { max_timestamp: i64, found: i64 },
+    BadHash,
+    BadProofOfWork,
+    BadMerkleRoot,
+    BadWitnessRoot,
+    DuplicateTransaction { tx_index: usize },
+    BadSignature { tx_index: usize },
+    BadTransfer { tx_index: usize, detail: &'static str },
+    MissingCoinbase,
+    MisplacedCoinbase { tx_index: usize },
+    BadCoinbaseHeight { expected: u32, found: u64 },
+    ExcessiveCoinbase { allowed: u64, found: u64 },
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::BadIndex { expected, found } => {
+                write!(f, "bad index: expected {}, found {}", expected, found)
+            }
+            BlockError::BadLink { expected, found } => {
+                write!(f, "bad previous hash: expected {}, found {}", expected, found)
+            }
+            BlockError::BadBits { expected, found } => {
+                write!(f, "bad target bits: expected {:#010x}, found {:#010x}", expected, found)
+            }
+            BlockError::TimeTooOld { median_time_past, found } => write!(
+                f,
+                "timestamp {} is not after the median time past {}",
+                found, median_time_past
+            ),
+            BlockError::TimeTooNew { max_timestamp, found } => {
+                write!(f, "timestamp {} is more than 2 hours ahead (max {})", found, max_timestamp)
+            }
+            BlockError::BadHash => write!(f, "stored hash does not match header"),
+            BlockError::BadProofOfWork => write!(f, "hash is above the target"),
+            BlockError::BadMerkleRoot => write!(f, "merkle root does not match transactions"),
+            BlockError::BadWitnessRoot => write!(f, "witness root does not match signatures"),
+            BlockError::DuplicateTransaction { tx_index } => {
+                write!(f, "transaction {} appears earlier in the block", tx_index)
+            }
+            BlockError::BadSignature { tx_index } => {
+                write!(f, "invalid signature in transaction {}", tx_index)
+            }
+            BlockError::BadTransfer { tx_index, detail } => {
+                write!(f, "transaction {} rejected: {}", tx_index, detail)
+            }
+            BlockError::MissingCoinbase => write!(f, "first transaction is not a coinbase"),
+            BlockError::MisplacedCoinbase { tx_index } => {
+                write!(f, "unexpected coinbase at transaction {}", tx_index)
+            }
+            BlockError::BadCoinbaseHeight { expected, found } => {
+                write!(f, "coinbase nonce {} does not match block height {}", found, expected)
+            }
+            BlockError::ExcessiveCoinbase { allowed, found } => write!(
+                f,
+                "coinbase pays {} but at most {} is allowed",
+                amount::format_amount(*found),
+                amount::format_amount(*allowed)
+            ),
         }
-        println!("Block Mined! Hash: {}", self.hash);
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// 链上第一个不合法的区块及其原因
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChainError {
+    pub height: usize,
+    #[serde(flatten)]
+    pub error: BlockError,
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "block {}: {}", self.height, self.error)
+    }
+}
+
+#[derive(Debug)]
 pub struct Blockchain {
     pub chain: Vec<Block>,
-    pub difficulty: usize,
-    pub pending_transactions: Vec<Transaction>,
+    pub difficulty_rules: DifficultyRules,
+    // chain_work[i] 为创世区块到第 i 个区块的累计工作量
+    pub chain_work: Vec<U256>,
+    // 主链区块的哈希到高度的索引
+    heights: HashMap<String, usize>,
+    // 主链交易的交易 ID 到区块高度的索引
+    tx_heights: HashMap<String, usize>,
+    // 不在主链上的已知区块（侧链），按哈希索引
+    side_blocks: HashMap<String, Block>,
+    pub policy: MonetaryPolicy,
+    // 主链上已确认交易的账本，随链尾变化增量更新
+    ledger: Ledger,
+    pub mempool: Mempool,
+    // 启用 UTXO 模型时主链上所有未花费的输出
+    pub utxo: Option<UtxoSet>,
+    store: Box<dyn BlockStore>,
 }
 
 impl Blockchain {
+    // 仅保存在内存中的区块链
+    // difficulty 为初始目标值对应的哈希前导 '0' 个数
     pub fn new(difficulty: usize) -> Self {
-        let genesis_block_tx = vec![Transaction {
-            sender: "System".to_string(),
-            receiver: "Creator".to_string(),
-            amount: 50.0,
-            signature: None,
-        }];
-        let mut genesis_block = Block::new(0, genesis_block_tx, "0".to_string());
-        genesis_block.mine(difficulty);
-        Blockchain {
+        let mut params = NetworkParams::mainnet();
+        params.genesis = GenesisConfig {
+            nonce: None,
+            bits: difficulty::bits_for_leading_zeros(difficulty),
+            ..params.genesis
+        };
+        Blockchain::open(&params, Box::new(MemoryStore)).expect("in-memory store never fails")
+    }
+
+    // 从存储中加载已有的区块和内存池，存储为空时写入创世区块
+    // 已有数据的创世区块与配置不同时拒绝加载
+    pub fn open(params: &NetworkParams, mut store: Box<dyn BlockStore>) -> io::Result<Self> {
+        let genesis = &params.genesis;
+        let policy = params.policy;
+        // 初始目标值由创世区块决定
+        let difficulty_rules = DifficultyRules {
+            initial_bits: genesis.bits,
+            ..params.difficulty_rules
+        };
+        if genesis.reward > policy.max_supply {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Genesis reward exceeds the maximum supply",
+            ));
+        }
+        genesis.validate(difficulty_rules.pow_limit())?;
+        let genesis_block = genesis.build()?;
+        if let Err(e) = genesis_block.validate(None, genesis.bits, i64::MIN) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid genesis block: {}", e),
+            ));
+        }
+
+        let mut stored_blocks = store.load_blocks()?.into_iter();
+        match stored_blocks.next() {
+            Some(block) if block.hash != genesis_block.hash => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Stored chain has genesis {}, expected {}",
+                        block.hash, genesis_block.hash
+                    ),
+                ));
+            }
+            Some(_) => {}
+            None => store.append_block(&genesis_block)?,
+        }
+        let saved_transactions = store.load_mempool()?;
+
+        let ledger = Ledger::from_chain(std::slice::from_ref(&genesis_block));
+        let mut blockchain = Blockchain {
+            chain_work: vec![genesis_block.header.work()],
+            heights: HashMap::from([(genesis_block.hash.clone(), 0)]),
+            tx_heights: genesis_block.transactions.iter().map(|tx| (tx.hash(), 0)).collect(),
             chain: vec![genesis_block],
-            difficulty,
-            pending_transactions: Vec::new(),
+            side_blocks: HashMap::new(),
+            difficulty_rules,
+            policy,
+            ledger,
+            mempool: Mempool::default(),
+            utxo: params.utxo.then(UtxoSet::default),
+            store,
+        };
+        if let Err(e) = blockchain.validate_chain() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+        }
+        if let Some(utxo) = &mut blockchain.utxo {
+            utxo.connect_block(&blockchain.chain[0])
+                .expect("genesis block was validated");
+        }
+
+        // 按写入顺序重新接入所有区块，重建侧链并选出工作量最大的链
+        for block in stored_blocks {
+            if let Err(e) = blockchain.accept_block(block, false) {
+                eprintln!("Skipping stored block: {}", e);
+            }
         }
+
+        // 重新校验保存的内存池交易，丢弃已经不再合法的
+        let ledger = blockchain.ledger.clone();
+        blockchain
+            .mempool
+            .restore(saved_transactions, ledger, blockchain.utxo.as_ref());
+        blockchain.store.save_mempool(&blockchain.mempool.saved())?;
+        Ok(blockchain)
+    }
+
+    pub fn genesis_hash(&self) -> &str {
+        &self.chain[0].hash
+    }
+
+    pub fn tip(&self) -> &Block {
+        self.chain.last().unwrap()
+    }
+
+    // 下一个区块需要使用的目标值
+    pub fn next_bits(&self) -> u32 {
+        self.difficulty_rules.next_bits(&self.chain)
+    }
+
+    // 链尾的累计工作量
+    pub fn total_work(&self) -> U256 {
+        self.chain_work.last().copied().unwrap_or_default()
     }
 
     pub fn add_transaction(&mut self, transaction: Transaction) -> Result<(), &'static str> {
+        if transaction.is_coinbase() {
+            return Err("System transactions cannot be submitted");
+        }
         if !transaction.is_valid() {
             return Err("Invalid transaction signature");
         }
-        self.pending_transactions.push(transaction);
+        let now = Utc::now().timestamp();
+        self.mempool.expire(now);
+        self.mempool.check_duplicate(&transaction)?;
+        self.check_model(&transaction)?;
+        // 余额要扣除内存池中尚未打包的支出，输入不能已被内存池中的交易花费
+        if let Some(view) = self.pending_utxo_view() {
+            view.check(&transaction)?;
+        }
+        self.pending_ledger().apply(&transaction)?;
+
+        let backup = self.mempool.clone();
+        self.mempool.insert(transaction, now)?;
+        if self.store.save_mempool(&self.mempool.saved()).is_err() {
+            self.mempool = backup;
+            return Err("Failed to persist mempool");
+        }
         Ok(())
     }
 
-    pub fn mine_pending_transactions(&mut self) -> Result<(), &'static str> {
-        if self.pending_transactions.is_empty() {
-            return Err("No transactions to mine");
+    // 修改内存池的容量限制
+    pub fn set_mempool_config(&mut self, config: MempoolConfig) {
+        self.mempool.set_config(config);
+        if self.store.save_mempool(&self.mempool.saved()).is_err() {
+            eprintln!("Failed to persist mempool");
+        }
+    }
+
+    // 高度 height 的区块 coinbase 允许领取的最大金额: 区块奖励加上手续费
+    // issued 为此前的流通总量，区块奖励不会使其超过最大供应量
+    pub fn block_reward(&self, height: u32, issued: u64, fees: u64) -> u64 {
+        let remaining = self.policy.max_supply.saturating_sub(issued);
+        self.policy.subsidy_at(height).min(remaining).saturating_add(fees)
+    }
+
+    // 用内存池中的交易构造一个待挖矿的区块模板，不修改链的状态
+    // 第一笔交易是支付给 miner_address 的 coinbase
+    pub fn block_template(&self, miner_address: &str) -> Result<Block, &'static str> {
+        if miner_address.is_empty() {
+            return Err("Miner address is required");
         }
 
+        // 手续费率高的交易优先打包
+        let selected = self.mempool.select_for_block();
+        let fees = selected.iter().fold(0u64, |sum, tx| sum.saturating_add(tx.fee));
+
+        let height = self.chain.len() as u32;
+        let reward = self.block_reward(height, self.ledger().issued(), fees);
+        let mut transactions = Vec::with_capacity(selected.len() + 1);
+        transactions.push(Transaction::coinbase(miner_address, reward, height));
+        transactions.extend(selected);
+
         let previous_hash = self.chain.last().unwrap().hash.clone();
-        let mut new_block = Block::new(
-            self.chain.len() as u32,
-            self.pending_transactions.clone(),
-            previous_hash,
-        );
-        new_block.mine(self.difficulty);
-        self.chain.push(new_block);
-        self.pending_transactions.clear();
+        let mut block = Block::new(height, self.next_bits(), transactions, previous_hash);
+        // 本地时钟可能落后于链上的中位时间（例如 regtest 连续出块）
+        let min_timestamp = difficulty::median_time_past(&self.chain).saturating_add(1);
+        if block.header.timestamp < min_timestamp {
+            block.header.timestamp = min_timestamp;
+            block.hash = block.header.calculate_hash();
+        }
+        Ok(block)
+    }
+
+    pub fn mine_pending_transactions(&mut self, miner_address: &str) -> Result<(), String> {
+        let mut new_block = self.block_template(miner_address)?;
+        new_block.mine(miner::default_threads());
+        self.submit_block(new_block).map(|_| ())
+    }
+
+    // 链尾变化后，移除已上链或不再合法的内存池交易
+    fn prune_mempool(&mut self) {
+        self.mempool.expire(Utc::now().timestamp());
+        self.mempool.revalidate(self.ledger.clone(), self.utxo.as_ref());
+        if self.store.save_mempool(&self.mempool.saved()).is_err() {
+            eprintln!("Failed to persist mempool after new block");
+        }
+    }
+
+    // 只包含已上链交易的账本
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    // 链上交易加上内存池交易之后的账本
+    pub fn pending_ledger(&self) -> Ledger {
+        let mut ledger = self.ledger.clone();
+        for tx in self.mempool.transactions() {
+            // 进入内存池前已经检查过余额
+            let _ = ledger.apply(tx);
+        }
+        ledger
+    }
+
+    // 主链 UTXO 集合加上内存池交易之后的视图，未启用 UTXO 模型时为 None
+    fn pending_utxo_view(&self) -> Option<UtxoView<'_>> {
+        let mut view = UtxoView::new(self.utxo.as_ref()?, true);
+        for tx in self.mempool.transactions() {
+            view.record(tx);
+        }
+        Some(view)
+    }
+
+    // 未启用 UTXO 模型的网络只接受账户模型的转账
+    fn check_model(&self, tx: &Transaction) -> Result<(), &'static str> {
+        if self.utxo.is_none() && tx.is_utxo() {
+            return Err("UTXO transactions are not enabled on this network");
+        }
         Ok(())
     }
+
+    fn check_block_model(&self, block: &Block) -> Result<(), BlockError> {
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            self.check_model(tx)
+                .map_err(|detail| BlockError::BadTransfer { tx_index, detail })?;
+        }
+        Ok(())
+    }
+
+    // 从创世区块开始逐个校验，返回第一个不合法的区块
+    pub fn validate_chain(&self) -> Result<(), ChainError> {
+        self.validate_blocks(&self.chain)
+    }
+
+    // 按当前的共识规则校验任意一条从创世区块开始的链
+    fn validate_blocks(&self, chain: &[Block]) -> Result<(), ChainError> {
+        let mut previous: Option<&Block> = None;
+        let mut ledger = Ledger::default();
+        let mut utxo = self.utxo.as_ref().map(|_| UtxoSet::default());
+        for (height, block) in chain.iter().enumerate() {
+            let expected_bits = self.difficulty_rules.next_bits(&chain[..height]);
+            let median_time_past = difficulty::median_time_past(&chain[..height]);
+            // 创世区块的奖励由创世配置固定，打开区块链时已与配置比对
+            let allowed = if height == 0 {
+                u64::MAX
+            } else {
+                self.block_reward(height as u32, ledger.issued(), block.total_fees())
+            };
+            block
+                .validate(previous, expected_bits, median_time_past)
+                .and_then(|_| block.validate_coinbase(allowed))
+                .map_err(|error| ChainError { height, error })?;
+            ledger
+                .apply_block(block)
+                .and_then(|_| self.check_block_model(block))
+                .and_then(|_| utxo.as_mut().map_or(Ok(()), |utxo| utxo.connect_block(block)))
+                .map_err(|error| ChainError { height, error })?;
+            previous = Some(block);
+        }
+        Ok(())
+    }
+
+    pub fn is_chain_valid(&self) -> bool {
+        self.validate_chain().is_ok()
+    }
 }
 
 struct AppState {
     blockchain: RwLock<Blockchain>,
+    // 同一时间只运行一个挖矿任务
+    mining: tokio::sync::Mutex<()>,
+    miner_threads: usize,
+    last_mining: RwLock<Option<MiningResult>>,
+    // 当前的挖矿任务
+    mining_job: Mutex<Option<MiningJob>>,
+    peers: p2p::Peers,
+    params: NetworkParams,
+    sync: Mutex<sync::HeaderSync>,
+}
+
+// 正在挖的区块模板
+struct MiningJob {
+    cancel: CancelToken,
+    // 模板中手续费率最低的交易，没有普通交易时为 None
+    lowest_fee_tx: Option<Transaction>,
+}
+
+impl AppState {
+    // 让正在进行的挖矿放弃当前模板，并基于最新的链尾和内存池重新开始
+    fn restart_mining(&self) {
+        if let Some(job) = self.mining_job.lock().unwrap().as_ref() {
+            job.cancel.cancel();
+        }
+    }
+
+    // 新交易进入内存池: 只有费率高于模板中最低费率的交易才重新构造模板
+    // 否则持续到达的交易会让挖矿不断重启而无法出块，交易留到下一个区块打包
+    fn transaction_accepted(&self, tx: &Transaction) {
+        if let Some(job) = self.mining_job.lock().unwrap().as_ref() {
+            // 模板中没有普通交易时，任何支付手续费的交易都值得打包
+            let better = match &job.lowest_fee_tx {
+                Some(lowest) => mempool::higher_fee_rate(tx, lowest),
+                None => tx.fee > 0,
+            };
+            if better {
+                job.cancel.cancel();
+            }
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    let blockchain = Blockchain::new(4);
+    let mut params = match NetworkParams::from_args(std::env::args().skip(1)) {
+        Ok(params) => params,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("usage: learn_blockchain [--network mainnet|testnet|regtest]");
+            std::process::exit(2);
+        }
+    };
+    // 环境变量可以覆盖所选网络的参数
+    if let Ok(dir) = std::env::var("BLOCKCHAIN_DATA_DIR") {
+        params.data_dir = dir;
+    }
+    let store = FileStore::open(&params.data_dir).expect("failed to open data directory");
+    let policy = &mut params.policy;
+    if let Ok(s) = std::env::var("BLOCK_SUBSIDY") {
+        policy.initial_subsidy = amount::parse_amount(&s).expect("invalid BLOCK_SUBSIDY");
+    }
+    if let Ok(s) = std::env::var("HALVING_INTERVAL") {
+        policy.halving_interval = s.parse().expect("invalid HALVING_INTERVAL");
+    }
+    if let Ok(s) = std::env::var("MAX_SUPPLY") {
+        policy.max_supply = amount::parse_amount(&s).expect("invalid MAX_SUPPLY");
+    }
+    let difficulty_rules = &mut params.difficulty_rules;
+    if let Ok(s) = std::env::var("RETARGET_INTERVAL") {
+        difficulty_rules.retarget_interval = s.parse().expect("invalid RETARGET_INTERVAL");
+    }
+    if let Ok(s) = std::env::var("TARGET_BLOCK_SECS") {
+        difficulty_rules.target_block_secs = s.parse().expect("invalid TARGET_BLOCK_SECS");
+    }
+    if let Ok(path) = std::env::var("GENESIS_CONFIG") {
+        params.genesis = match GenesisConfig::load(path) {
+            Ok(genesis) => genesis,
+            Err(e) => {
+                eprintln!("Invalid GENESIS_CONFIG: {}", e);
+                std::process::exit(1);
+            }
+        };
+    }
+    if std::env::var("UTXO_MODEL").is_ok_and(|s| s == "1" || s == "true") {
+        params.utxo = true;
+    }
+    let mut blockchain = match Blockchain::open(&params, Box::new(store)) {
+        Ok(blockchain) => blockchain,
+        Err(e) => {
+            eprintln!("Failed to load blockchain: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut mempool_config = MempoolConfig::default();
+    if let Ok(s) = std::env::var("MEMPOOL_MAX_TXS") {
+        mempool_config.max_count = s.parse().expect("invalid MEMPOOL_MAX_TXS");
+    }
+    if let Ok(s) = std::env::var("MEMPOOL_MAX_BYTES") {
+        mempool_config.max_bytes = s.parse().expect("invalid MEMPOOL_MAX_BYTES");
+    }
+    if let Ok(s) = std::env::var("MEMPOOL_EXPIRY_SECS") {
+        mempool_config.expiry_secs = s.parse().expect("invalid MEMPOOL_EXPIRY_SECS");
+    }
+    blockchain.set_mempool_config(mempool_config);
+    println!(
+        "Loaded {} {} blocks from {}",
+        blockchain.chain.len(),
+        params.network,
+        params.data_dir
+    );
+    let miner_threads = std::env::var("MINER_THREADS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or_else(miner::default_threads);
+    if let Ok(s) = std::env::var("HTTP_PORT") {
+        params.http_port = s.parse().expect("invalid HTTP_PORT");
+    }
+    if let Ok(s) = std::env::var("P2P_PORT") {
+        params.p2p_port = s.parse().expect("invalid P2P_PORT");
+    }
+    let http_port = params.http_port;
+    let p2p_port = params.p2p_port;
+    // 逗号分隔的节点地址，例如 127.0.0.1:4001,127.0.0.1:4002
+    let peer_addrs: Vec<String> = std::env::var("PEERS")
+        .map(|s| s.split(',').filter(|p| !p.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default();
     let shared_state = Arc::new(AppState {
         blockchain: RwLock::new(blockchain),
+        mining: tokio::sync::Mutex::new(()),
+        miner_threads,
+        last_mining: RwLock::new(None),
+        mining_job: Mutex::new(None),
+        peers: p2p::Peers::default(),
+        params,
+        sync: Mutex::new(sync::HeaderSync::default()),
+    });
+
+    let p2p_state = shared_state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = p2p::listen(p2p_state, p2p_port).await {
+            eprintln!("P2P listener failed: {}", e);
+        }
     });
+    tokio::spawn(p2p::run_sync(shared_state.clone()));
+    for peer in peer_addrs {
+        tokio::spawn(p2p::connect(shared_state.clone(), peer));
+    }
 
     let app = Router::new()
-        .route("/blocks", get(get_blocks))
+        .route("/blocks", get(get_blocks).post(submit_block))
+        .route("/blocks/tip", get(get_tip))
+        .route("/blocks/:height", get(get_block_by_height))
+        .route("/blocks/hash/:hash", get(get_block_by_hash))
         .route("/transactions", post(add_transaction))
+        .route("/transactions/raw", post(add_raw_transaction))
         .route("/mine", post(mine_block))
+        .route("/generate", post(generate_blocks))
+        .route("/mining/stats", get(get_mining_stats))
+        .route("/chain/verify", get(verify_chain))
+        .route("/balance/:address", get(get_balance))
+        .route("/utxos/:address", get(get_utxos))
+        .route("/tx/:hash/proof", get(get_tx_proof))
+        .route("/proofs/verify", post(verify_tx_proof))
+        .route("/supply", get(get_supply))
+        .route("/mempool", get(get_mempool))
+        .route("/peers", get(get_peers))
+        .route("/sync", get(get_sync))
+        .route("/network", get(get_network))
         .layer(CorsLayer::permissive())
         .with_state(shared_state);
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await.unwrap();
-    println!("Blockchain server running on http://127.0.0.1:3000");
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", http_port)).await.unwrap();
+    println!("Blockchain server running on http://127.0.0.1:{}", http_port);
     axum::serve(listener, app).await.unwrap();
 }
 
-async fn get_blocks(State(state): State<Arc<AppState>>) -> Json<Vec<Block>> {
+// 单页最多返回的区块数量
+const MAX_BLOCKS_PER_PAGE: usize = 500;
+
+#[derive(Deserialize)]
+struct BlockPage {
+    #[serde(default)]
+    from: usize, // 起始高度
+    limit: Option<usize>,
+}
+
+// 从 from 开始按高度升序返回最多 limit 个主链区块
+async fn get_blocks(
+    State(state): State<Arc<AppState>>,
+    Query(page): Query<BlockPage>,
+) -> Json<Vec<Block>> {
+    let limit = page.limit.unwrap_or(100).min(MAX_BLOCKS_PER_PAGE);
+    let bc = state.blockchain.read().unwrap();
+    Json(bc.chain.iter().skip(page.from).take(limit).cloned().collect())
+}
+
+async fn get_block_by_height(
+    State(state): State<Arc<AppState>>,
+    Path(height): Path<usize>,
+) -> Result<Json<Block>, (StatusCode, String)> {
+    let bc = state.blockchain.read().unwrap();
+    bc.chain
+        .get(height)
+        .cloned()
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "No block at this height".to_string()))
+}
+
+async fn get_block_by_hash(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<Json<Block>, (StatusCode, String)> {
     let bc = state.blockchain.read().unwrap();
-    Json(bc.chain.clone())
+    bc.height_of(&hash)
+        .map(|height| Json(bc.chain[height].clone()))
+        .ok_or((StatusCode::NOT_FOUND, "Block not found in the main chain".to_string()))
+}
+
+async fn get_tip(State(state): State<Arc<AppState>>) -> Json<Block> {
+    Json(state.blockchain.read().unwrap().tip().clone())
+}
+
+// 接收其他节点挖出的区块
+async fn submit_block(
+    State(state): State<Arc<AppState>>,
+    Json(block): Json<Block>,
+) -> Result<Json<BlockStatus>, (StatusCode, String)> {
+    let hash = block.hash.clone();
+    let mut bc = state.blockchain.write().unwrap();
+    let status = bc
+        .submit_block(block)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if status != BlockStatus::SideChain {
+        state.restart_mining();
+    }
+    state.peers.announce_block(&hash, None);
+    Ok(Json(status))
 }
 
 async fn add_transaction(
     State(state): State<Arc<AppState>>,
     Json(tx): Json<Transaction>,
 ) -> Result<Json<String>, (StatusCode, String)> {
+    let hash = tx.hash();
     let mut bc = state.blockchain.write().unwrap();
-    match bc.add_transaction(tx) {
-        Ok(_) => Ok(Json("Transaction added to mempool".to_string())),
+    match bc.add_transaction(tx.clone()) {
+        Ok(_) => {
+            state.transaction_accepted(&tx);
+            state.peers.announce_transaction(&hash, None);
+            Ok(Json("Transaction added to mempool".to_string()))
+        }
         Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
     }
 }
 
-async fn mine_block(State(state): State<Arc<AppState>>) -> Result<Json<Block>, (StatusCode, String)> {
-    let mut bc = state.blockchain.write().unwrap();
-    match bc.mine_pending_transactions() {
-        Ok(_) => {
-            let latest_block = bc.chain.last().unwrap().clone();
-            Ok(Json(latest_block))
-        },
-        Err(e) => Err((StatusCode::BAD_REQUEST, e.to_string())),
+#[derive(Deserialize)]
+struct RawTransaction {
+    hex: String, // 规范二进制编码的 Hex 字符串
+}
+
+async fn add_raw_transaction(
+    state: State<Arc<AppState>>,
+    Json(raw): Json<RawTransaction>,
+) -> Result<Json<String>, (StatusCode, String)> {
+    let bytes = hex::decode(&raw.hex)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid hex".to_string()))?;
+    let tx = Transaction::from_bytes(&bytes)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    add_transaction(state, Json(tx)).await
+}
+
+#[derive(Deserialize)]
+struct MineRequest {
+    miner_address: String, // 接收区块奖励的地址
+}
+
+async fn mine_block(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<MineRequest>,
+) -> Result<Json<Block>, (StatusCode, String)> {
+    let _mining = state.mining.lock().await;
+    mine_next_block(&state, &req.miner_address).await.map(Json)
+}
+
+// 单次 /generate 最多挖出的区块数量
+const MAX_GENERATE_BLOCKS: u32 = 1000;
+
+#[derive(Deserialize)]
+struct GenerateRequest {
+    miner_address: String,
+    count: u32,
+}
+
+// 立即连续生成 count 个区块，只在 regtest 网络可用
+async fn generate_blocks(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<GenerateRequest>,
+) -> Result<Json<Vec<Block>>, (StatusCode, String)> {
+    if state.params.network != Network::Regtest {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Block generation is only available on regtest".to_string(),
+        ));
+    }
+    if req.count > MAX_GENERATE_BLOCKS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {} blocks can be generated per request", MAX_GENERATE_BLOCKS),
+        ));
+    }
+    let _mining = state.mining.lock().await;
+    let mut blocks = Vec::new();
+    for _ in 0..req.count {
+        blocks.push(mine_next_block(&state, &req.miner_address).await?);
     }
-}
\ No newline at end of file
+    Ok(Json(blocks))
+}
+
+// 基于当前链尾挖出一个区块并接入主链，调用方需持有挖矿锁
+async fn mine_next_block(state: &AppState, miner_address: &str) -> Result<Block, (StatusCode, String)> {
+    loop {
+        // 只在读锁下取出区块模板，挖矿期间不持有任何锁
+        let mut block = {
+            let bc = state.blockchain.read().unwrap();
+            bc.block_template(miner_address)
+                .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+        };
+        let cancel = CancelToken::default();
+        *state.mining_job.lock().unwrap() = Some(MiningJob {
+            cancel: cancel.clone(),
+            lowest_fee_tx: mempool::lowest_fee_rate(&block.transactions[1..]).cloned(),
+        });
+
+        let threads = state.miner_threads;
+        let (block, result) = tokio::task::spawn_blocking(move || {
+            let result = block.mine_cancellable(threads, &cancel);
+            (block, result)
+        })
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        *state.mining_job.lock().unwrap() = None;
+
+        let Some(result) = result else {
+            println!("Mining restarted: chain tip or mempool changed");
+            continue;
+        };
+        *state.last_mining.write().unwrap() = Some(result);
+
+        let mut bc = state.blockchain.write().unwrap();
+        match bc.submit_block(block.clone()) {
+            // 挖矿期间链尾已经变化，区块只进入了侧链，基于新的链尾重新挖矿
+            Ok(BlockStatus::SideChain) => println!("Mined block is stale, restarting"),
+            Ok(_) => {
+                state.peers.announce_block(&block.hash, None);
+                return Ok(block);
+            }
+            Err(e) => return Err((StatusCode::CONFLICT, e)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct MiningStats {
+    threads: usize,
+    bits: u32,
+    target: Option<U256>,
+    rules: DifficultyRules,
+    last: Option<MiningResult>,
+}
+
+async fn get_mining_stats(State(state): State<Arc<AppState>>) -> Json<MiningStats> {
+    let (bits, rules) = {
+        let bc = state.blockchain.read().unwrap();
+        (bc.next_bits(), bc.difficulty_rules)
+    };
+    Json(MiningStats {
+        threads: state.miner_threads,
+        bits,
+        target: U256::from_compact(bits),
+        rules,
+        last: state.last_mining.read().unwrap().clone(),
+    })
+}
+
+#[derive(Serialize)]
+struct ChainReport {
+    valid: bool,
+    length: usize,
+    total_work: U256,
+    error: Option<ChainError>,
+}
+
+async fn verify_chain(State(state): State<Arc<AppState>>) -> Json<ChainReport> {
+    let bc = state.blockchain.read().unwrap();
+    let error = bc.validate_chain().err();
+    Json(ChainReport {
+        valid: error.is_none(),
+        length: bc.chain.len(),
+        total_work: bc.total_work(),
+        error,
+    })
+}
+
+#[derive(Serialize)]
+struct BalanceResponse {
+    address: String,
+    #[serde(with = "amount::decimal")]
+    confirmed: u64,
+    #[serde(with = "amount::decimal")]
+    available: u64,
+    next_nonce: u64,
+}
+
+async fn get_balance(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+) -> Json<BalanceResponse> {
+    let bc = state.blockchain.read().unwrap();
+    let pending = bc.pending_ledger();
+    Json(BalanceResponse {
+        confirmed: bc.ledger().balance(&address),
+        available: pending.balance(&address),
+        next_nonce: pending.next_nonce(&address),
+        address,
+    })
+}
+
+#[derive(Serialize)]
+struct UnspentOutput {
+    #[serde(flatten)]
+    outpoint: OutPoint,
+    #[serde(with = "amount::decimal")]
+    amount: u64,
+}
+
+#[derive(Serialize)]
+struct UtxoResponse {
+    address: String,
+    #[serde(with = "amount::decimal")]
+    total: u64,
+    outputs: Vec<UnspentOutput>,
+}
+
+// 地址在主链上的未花费输出，只在启用 UTXO 模型时可用
+async fn get_utxos(
+    State(state): State<Arc<AppState>>,
+    Path(address): Path<String>,
+) -> Result<Json<UtxoResponse>, (StatusCode, String)> {
+    let bc = state.blockchain.read().unwrap();
+    let utxo = bc.utxo.as_ref().ok_or((
+        StatusCode::NOT_FOUND,
+        "UTXO model is not enabled on this network".to_string(),
+    ))?;
+    let outputs: Vec<UnspentOutput> = utxo
+        .unspent(&address)
+        .into_iter()
+        .map(|(outpoint, out)| UnspentOutput { outpoint, amount: out.amount })
+        .collect();
+    Ok(Json(UtxoResponse {
+        total: outputs.iter().map(|out| out.amount).sum(),
+        address,
+        outputs,
+    }))
+}
+
+#[derive(Serialize)]
+struct TxProofResponse {
+    block_hash: String,
+    height: usize,
+    #[serde(flatten)]
+    proof: MerkleProof,
+}
+
+// 主链上某笔交易的 Merkle 证明
+async fn get_tx_proof(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<Json<TxProofResponse>, (StatusCode, String)> {
+    let bc = state.blockchain.read().unwrap();
+    bc.tx_height(&hash)
+        .and_then(|height| {
+            let block = &bc.chain[height];
+            block.merkle_proof(&hash).map(|proof| TxProofResponse {
+                block_hash: block.hash.clone(),
+                height,
+                proof,
+            })
+        })
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Transaction not found in the main chain".to_string()))
+}
+
+#[derive(Serialize)]
+struct ProofVerification {
+    valid: bool,
+    block_hash: String,
+    height: Option<usize>, // 区块在本节点主链上的高度
+}
+
+// 证明本身成立，且区块头属于本节点的主链；否则任何人都能构造满足自身目标值的区块头
+async fn verify_tx_proof(
+    State(state): State<Arc<AppState>>,
+    Json(proof): Json<MerkleProof>,
+) -> Json<ProofVerification> {
+    let block_hash = proof.header.calculate_hash();
+    let height = state.blockchain.read().unwrap().height_of(&block_hash);
+    Json(ProofVerification {
+        valid: height.is_some() && proof.verify(),
+        block_hash,
+        height,
+    })
+}
+
+async fn get_supply(State(state): State<Arc<AppState>>) -> Json<SupplyReport> {
+    let bc = state.blockchain.read().unwrap();
+    Json(supply::audit(&bc.chain, &bc.policy))
+}
+
+#[derive(Serialize)]
+struct MempoolInfo {
+    count: usize,
+    bytes: usize,
+    transactions: Vec<Transaction>,
+}
+
+async fn get_mempool(State(state): State<Arc<AppState>>) -> Json<MempoolInfo> {
+    let bc = state.blockchain.read().unwrap();
+    Json(MempoolInfo {
+        count: bc.mempool.len(),
+        bytes: bc.mempool.bytes(),
+        transactions: bc.mempool.select_for_block(),
+    })
+}
+
+async fn get_peers(State(state): State<Arc<AppState>>) -> Json<Vec<p2p::PeerInfo>> {
+    Json(state.peers.list())
+}
+
+async fn get_sync(State(state): State<Arc<AppState>>) -> Json<sync::SyncProgress> {
+    let bc = state.blockchain.read().unwrap();
+    let progress = state.sync.lock().unwrap().progress(&bc);
+    Json(progress)
+}
+
+async fn get_network(State(state): State<Arc<AppState>>) -> Json<NetworkParams> {
+    Json(state.params.clone())
+}
@@ -0,0 +1,367 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use crate::encoding::{Decode, Encode, Reader, Writer};
+use crate::ledger::Ledger;
+use crate::utxo::{UtxoSet, UtxoView};
+use crate::Transaction;
+
+#[derive(Debug, Clone, Copy)]
+pub struct MempoolConfig {
+    pub max_count: usize,
+    pub max_bytes: usize,
+    // 交易在内存池中停留超过该秒数后被丢弃
+    pub expiry_secs: i64,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            max_count: 5_000,
+            max_bytes: 1_000_000,
+            expiry_secs: 72 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MempoolEntry {
+    tx: Transaction,
+    hash: String,
+    size: usize,
+    added_at: i64,
+}
+
+// 持久化的内存池交易，连同进入内存池的时间，重启后过期时间不会重新计算
+#[derive(Debug, Clone)]
+pub struct SavedTransaction {
+    pub tx: Transaction,
+    pub added_at: i64,
+}
+
+impl Encode for SavedTransaction {
+    fn encode(&self, w: &mut Writer) {
+        self.tx.encode(w);
+        w.put_i64(self.added_at);
+    }
+}
+
+impl Decode for SavedTransaction {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str> {
+        Ok(SavedTransaction {
+            tx: Transaction::decode(r)?,
+            added_at: r.get_i64()?,
+        })
+    }
+}
+
+// 待打包交易池: 限制总数与总字节数，满时驱逐手续费率最低的交易，并丢弃过期交易
+// 同一发送者的交易总是按 nonce 递增的顺序进入内存池
+#[derive(Debug, Clone, Default)]
+pub struct Mempool {
+    pub config: MempoolConfig,
+    entries: Vec<MempoolEntry>,
+    bytes: usize,
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool {
+            config,
+            ..Mempool::default()
+        }
+    }
+
+    // 修改容量限制，超出新限制的交易按手续费率从低到高被驱逐
+    pub fn set_config(&mut self, config: MempoolConfig) {
+        self.config = config;
+        let entries = std::mem::take(&mut self.entries);
+        self.bytes = 0;
+        for entry in entries {
+            let _ = self.insert(entry.tx, entry.added_at);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    // 按进入内存池的顺序返回交易
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.entries.iter().map(|entry| &entry.tx)
+    }
+
+    pub fn to_vec(&self) -> Vec<Transaction> {
+        self.transactions().cloned().collect()
+    }
+
+    // 按进入内存池的顺序返回交易及其进入时间，用于持久化
+    pub fn saved(&self) -> Vec<SavedTransaction> {
+        self.entries
+            .iter()
+            .map(|entry| SavedTransaction {
+                tx: entry.tx.clone(),
+                added_at: entry.added_at,
+            })
+            .collect()
+    }
+
+    // 打包区块时使用的交易顺序
+    pub fn select_for_block(&self) -> Vec<Transaction> {
+        order_by_fee_rate(&self.to_vec())
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.get(hash).is_some()
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&Transaction> {
+        self.entries
+            .iter()
+            .find(|entry| entry.hash == hash)
+            .map(|entry| &entry.tx)
+    }
+
+    // 拒绝与内存池中已有交易重复的交易
+    pub fn check_duplicate(&self, tx: &Transaction) -> Result<(), &'static str> {
+        if self.contains(&tx.hash()) {
+            return Err("Transaction already in mempool");
+        }
+        if self
+            .entries
+            .iter()
+            .any(|entry| entry.tx.sender == tx.sender && entry.tx.nonce == tx.nonce)
+        {
+            return Err("Duplicate nonce: a transaction with this nonce is already pending");
+        }
+        Ok(())
+    }
+
+    // 加入一笔已通过账本校验的交易，返回为腾出空间而被驱逐的交易
+    pub fn insert(&mut self, tx: Transaction, now: i64) -> Result<Vec<Transaction>, &'static str> {
+        self.check_duplicate(&tx)?;
+        let size = tx.size();
+        if size > self.config.max_bytes {
+            return Err("Transaction is larger than the mempool");
+        }
+
+        // 先选出要驱逐的交易，空间足够时才真正移除，失败时内存池保持不变
+        let mut doomed = vec![false; self.entries.len()];
+        let mut roots = Vec::new();
+        let (mut count, mut bytes) = (self.entries.len(), self.bytes);
+        while count + 1 > self.config.max_count || bytes + size > self.config.max_bytes {
+            // 不驱逐同一发送者的交易，否则新交易的 nonce 会出现缺口
+            let victim = self
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(i, entry)| !doomed[*i] && entry.tx.sender != tx.sender)
+                .min_by(|(_, a), (_, b)| compare_fee_rate(&a.tx, a.size, &b.tx, b.size))
+                .map(|(i, _)| i);
+            match victim {
+                Some(i) if compare_fee_rate(&self.entries[i].tx, self.entries[i].size, &tx, size)
+                    == Ordering::Less =>
+                {
+                    let (sender, nonce) = (&self.entries[i].tx.sender, self.entries[i].tx.nonce);
+                    for (j, entry) in self.entries.iter().enumerate() {
+                        if !doomed[j] && entry.tx.sender == *sender && entry.tx.nonce >= nonce {
+                            doomed[j] = true;
+                            count -= 1;
+                            bytes -= entry.size;
+                        }
+                    }
+                    roots.push((sender.clone(), nonce));
+                }
+                _ => return Err("Mempool is full and the fee rate is too low"),
+            }
+        }
+
+        let mut evicted = Vec::new();
+        for (sender, nonce) in roots {
+            let root = self
+                .entries
+                .iter()
+                .position(|entry| entry.tx.sender == sender && entry.tx.nonce == nonce);
+            // 同一发送者更早的交易已被驱逐时，它已随之移除
+            if let Some(i) = root {
+                evicted.extend(self.remove_with_descendants(i).into_iter().map(|entry| entry.tx));
+            }
+        }
+
+        self.push(tx, now);
+        Ok(evicted)
+    }
+
+    // 移除停留时间超过 expiry_secs 的交易，返回移除的数量
+    pub fn expire(&mut self, now: i64) -> usize {
+        let before = self.entries.len();
+        while let Some(i) = self
+            .entries
+            .iter()
+            .position(|entry| now - entry.added_at > self.config.expiry_secs)
+        {
+            self.remove_with_descendants(i);
+        }
+        before - self.entries.len()
+    }
+
+    // 新区块接入后，基于最新的账本（和 UTXO 集合）重新校验剩余交易，返回移除的数量
+    pub fn revalidate(&mut self, mut ledger: Ledger, utxo: Option<&UtxoSet>) -> usize {
+        let before = self.entries.len();
+        let mut view = utxo.map(|set| UtxoView::new(set, true));
+        self.entries
+            .retain(|entry| accept(&mut ledger, view.as_mut(), &entry.tx));
+        self.bytes = self.entries.iter().map(|entry| entry.size).sum();
+        before - self.entries.len()
+    }
+
+    // 把交易放回内存池（重组时断开区块中的交易，或启动时保存的交易），它们排在原有交易之前
+    // 每笔交易保留原来的进入时间
+    pub fn restore(&mut self, transactions: Vec<SavedTransaction>, mut ledger: Ledger, utxo: Option<&UtxoSet>) {
+        let mut view = utxo.map(|set| UtxoView::new(set, true));
+        let existing = std::mem::take(&mut self.entries);
+        self.bytes = 0;
+        let candidates = transactions
+            .into_iter()
+            .map(|saved| (saved.tx, saved.added_at))
+            .chain(existing.into_iter().map(|entry| (entry.tx, entry.added_at)));
+        for (tx, added_at) in candidates {
+            // 只有真正进入内存池的交易才记入账本，避免同一发送者的 nonce 出现缺口
+            let mut next = ledger.clone();
+            if view.as_ref().is_some_and(|view| view.check(&tx).is_err()) || next.apply(&tx).is_err() {
+                continue;
+            }
+            if self.insert(tx.clone(), added_at).is_ok() {
+                ledger = next;
+                if let Some(view) = view.as_mut() {
+                    view.record(&tx);
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, tx: Transaction, added_at: i64) {
+        let size = tx.size();
+        self.bytes += size;
+        self.entries.push(MempoolEntry {
+            hash: tx.hash(),
+            tx,
+            size,
+            added_at,
+        });
+    }
+
+    // 移除第 i 笔交易以及同一发送者 nonce 更大的交易
+    fn remove_with_descendants(&mut self, i: usize) -> Vec<MempoolEntry> {
+        let sender = self.entries[i].tx.sender.clone();
+        let nonce = self.entries[i].tx.nonce;
+        let (removed, kept) = self
+            .entries
+            .drain(..)
+            .partition(|entry| entry.tx.sender == sender && entry.tx.nonce >= nonce);
+        self.entries = kept;
+        self.bytes = self.entries.iter().map(|entry| entry.size).sum();
+        removed
+    }
+}
+
+// 交易同时通过 UTXO 检查和账本检查时记入两者
+fn accept(ledger: &mut Ledger, view: Option<&mut UtxoView>, tx: &Transaction) -> bool {
+    if view.as_ref().is_some_and(|view| view.check(tx).is_err()) || ledger.apply(tx).is_err() {
+        return false;
+    }
+    if let Some(view) = view {
+        view.record(tx);
+    }
+    true
+}
+
+// 按手续费率（每字节手续费）从高到低排列交易，同时保证同一发送者的交易按 nonce 递增
+fn order_by_fee_rate(transactions: &[Transaction]) -> Vec<Transaction> {
+    let mut by_sender: HashMap<&str, Vec<&Transaction>> = HashMap::new();
+    for tx in transactions {
+        by_sender.entry(tx.sender.as_str()).or_default().push(tx);
+    }
+    let mut queues: HashMap<&str, VecDeque<&Transaction>> = by_sender
+        .into_iter()
+        .map(|(sender, mut txs)| {
+            txs.sort_by_key(|tx| tx.nonce);
+            (sender, txs.into())
+        })
+        .collect();
+
+    // 堆中只放每个发送者当前可打包的第一笔交易
+    let mut heap: BinaryHeap<Candidate<'_>> = queues
+        .values_mut()
+        .filter_map(|queue| queue.pop_front().map(Candidate::new))
+        .collect();
+
+    let mut ordered = Vec::with_capacity(transactions.len());
+    while let Some(Candidate { tx, .. }) = heap.pop() {
+        ordered.push(tx.clone());
+        if let Some(next) = queues.get_mut(tx.sender.as_str()).and_then(|q| q.pop_front()) {
+            heap.push(Candidate::new(next));
+        }
+    }
+    ordered
+}
+
+struct Candidate<'a> {
+    tx: &'a Transaction,
+    size: usize,
+}
+
+impl<'a> Candidate<'a> {
+    fn new(tx: &'a Transaction) -> Self {
+        Candidate { tx, size: tx.size() }
+    }
+}
+
+// transactions 中手续费率最低的交易
+pub fn lowest_fee_rate(transactions: &[Transaction]) -> Option<&Transaction> {
+    transactions
+        .iter()
+        .map(Candidate::new)
+        .min()
+        .map(|candidate| candidate.tx)
+}
+
+// a 的手续费率是否严格高于 b
+pub fn higher_fee_rate(a: &Transaction, b: &Transaction) -> bool {
+    compare_fee_rate(a, a.size(), b, b.size()) == Ordering::Greater
+}
+
+// 交叉相乘比较 fee / size，避免浮点误差
+fn compare_fee_rate(a: &Transaction, a_size: usize, b: &Transaction, b_size: usize) -> Ordering {
+    let lhs = a.fee as u128 * b_size as u128;
+    let rhs = b.fee as u128 * a_size as u128;
+    lhs.cmp(&rhs)
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_fee_rate(self.tx, self.size, other.tx, other.size)
+    }
+}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate<'_> {}
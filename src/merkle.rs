@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::BlockHeader;
+
+// 交易哈希的 Merkle 树
+//
+// 每一层相邻的两个哈希拼接后再哈希得到上一层，节点数为奇数时最后一个节点直接进入上一层
+// 不复制最后一个节点: 否则 [A, B, C] 与 [A, B, C, C] 的根相同 (CVE-2012-2459)
+// 证明交易属于某个区块只需要从叶子到根路径上的兄弟节点（Merkle 分支）、叶子总数和区块头
+
+fn hash_pair(left: &str, right: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+// 由下一层计算上一层
+fn next_level(level: &[String]) -> Vec<String> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_pair(left, right),
+            _ => pair[0].clone(),
+        })
+        .collect()
+}
+
+// 空树的根为 "0"
+pub fn merkle_root(leaves: Vec<String>) -> String {
+    let mut level = leaves;
+    if level.is_empty() {
+        return String::from("0");
+    }
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.remove(0)
+}
+
+// 第 index 个叶子的 Merkle 分支，按从叶子到根的顺序排列
+pub fn merkle_branch(leaves: &[String], mut index: usize) -> Option<Vec<String>> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let mut branch = Vec::new();
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        // 直接进入上一层的节点没有兄弟节点
+        if let Some(sibling) = level.get(index ^ 1) {
+            branch.push(sibling.clone());
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    Some(branch)
+}
+
+// 沿分支从叶子计算到根，index 的每一位表示该层当前节点在左还是在右
+// leaf_count 决定每一层的节点数，从而知道哪些层没有兄弟节点
+pub fn verify_merkle_branch(
+    leaf: &str,
+    mut index: usize,
+    leaf_count: usize,
+    branch: &[String],
+    root: &str,
+) -> bool {
+    if index >= leaf_count {
+        return false;
+    }
+    let mut hash = leaf.to_string();
+    let mut width = leaf_count;
+    let mut siblings = branch.iter();
+    while width > 1 {
+        if index ^ 1 < width {
+            let Some(sibling) = siblings.next() else {
+                return false;
+            };
+            hash = if index.is_multiple_of(2) {
+                hash_pair(&hash, sibling)
+            } else {
+                hash_pair(sibling, &hash)
+            };
+        }
+        index /= 2;
+        width = width.div_ceil(2);
+    }
+    siblings.next().is_none() && hash == root
+}
+
+// 交易包含在区块中的证明
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MerkleProof {
+    pub tx_hash: String,
+    pub index: usize,    // 交易在区块中的位置
+    pub tx_count: usize, // 区块中的交易总数
+    pub branch: Vec<String>,
+    pub header: BlockHeader,
+}
+
+impl MerkleProof {
+    // 分支能还原出区块头中的 Merkle 根，并且区块头满足自身的工作量证明
+    pub fn verify(&self) -> bool {
+        self.header.meets_target()
+            && verify_merkle_branch(
+                &self.tx_hash,
+                self.index,
+                self.tx_count,
+                &self.branch,
+                &self.header.merkle_root,
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Block, BlockError, Transaction};
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("{:064x}", i)).collect()
+    }
+
+    // regtest 难度下挖出的高度为 0 的区块
+    fn mined_block(transactions: Vec<Transaction>) -> Block {
+        let mut block = Block::new(0, 0x207f_ffff, transactions, "0".to_string());
+        block.mine(1);
+        block
+    }
+
+    #[test]
+    fn duplicated_last_leaf_changes_root() {
+        let abc = leaves(3);
+        let mut abcc = abc.clone();
+        abcc.push(abc[2].clone());
+        assert_ne!(merkle_root(abc), merkle_root(abcc));
+    }
+
+    #[test]
+    fn block_with_duplicate_transaction_is_rejected() {
+        let coinbase = Transaction::coinbase("miner", 50, 0);
+        let tx = Transaction::coinbase("other", 1, 1);
+        let block = mined_block(vec![coinbase, tx.clone(), tx]);
+        assert_eq!(
+            block.validate(None, 0x207f_ffff, i64::MIN),
+            Err(BlockError::DuplicateTransaction { tx_index: 2 })
+        );
+    }
+
+    #[test]
+    fn branch_round_trips_for_odd_leaf_counts() {
+        for n in [1, 3, 5, 7, 9] {
+            let leaves = leaves(n);
+            let root = merkle_root(leaves.clone());
+            for (index, leaf) in leaves.iter().enumerate() {
+                let branch = merkle_branch(&leaves, index).unwrap();
+                assert!(verify_merkle_branch(leaf, index, n, &branch, &root), "n={} index={}", n, index);
+                if n > 1 {
+                    let other = &leaves[(index + 1) % n];
+                    assert!(!verify_merkle_branch(other, index, n, &branch, &root));
+                }
+            }
+            assert!(merkle_branch(&leaves, n).is_none());
+        }
+    }
+
+    #[test]
+    fn block_proof_verifies_for_odd_transaction_count() {
+        let txs: Vec<Transaction> = (0..3).map(|i| Transaction::coinbase("miner", 50, i)).collect();
+        let block = mined_block(txs.clone());
+        for tx in &txs {
+            let proof = block.merkle_proof(&tx.hash()).unwrap();
+            assert_eq!(proof.tx_count, 3);
+            assert!(proof.verify());
+        }
+    }
+}
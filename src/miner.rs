@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::encoding::Encode;
+use crate::target::U256;
+use crate::BlockHeader;
+
+// 一次挖矿的结果与统计
+#[derive(Serialize, Debug, Clone)]
+pub struct MiningResult {
+    pub nonce: u64,
+    pub hash: String,
+    pub threads: usize,
+    pub hashes: u64,
+    pub elapsed_ms: u128,
+    pub hashrate: f64, // 每秒哈希次数
+}
+
+// 通知挖矿线程放弃当前模板，例如链尾变化或有新交易到达时
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// 默认使用全部可用的 CPU 核心
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// 在多个线程间划分 nonce 空间，搜索作为 256 位整数不超过 target 的哈希:
+// 第 i 个线程尝试 start + i, start + i + N, start + i + 2N, ...
+// 任一线程找到结果后，其余线程随即停止；被取消时返回 None
+pub fn search_nonce(
+    header: &BlockHeader,
+    target: U256,
+    threads: usize,
+    cancel: &CancelToken,
+) -> Option<MiningResult> {
+    let threads = threads.max(1);
+
+    // nonce 是区块头编码的最后 8 个字节，其余部分只需哈希一次
+    let mut prefix = header.to_bytes();
+    prefix.truncate(prefix.len() - 8);
+    let mut base = Sha256::new();
+    base.update(&prefix);
+
+    let found = AtomicBool::new(false);
+    let hashes = AtomicU64::new(0);
+    let solution: Mutex<Option<(u64, String)>> = Mutex::new(None);
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for i in 0..threads {
+            let (base, found, hashes, solution) = (&base, &found, &hashes, &solution);
+            scope.spawn(move || {
+                let mut nonce = header.nonce.wrapping_add(i as u64);
+                let mut count = 0u64;
+                while !found.load(Ordering::Relaxed) && !cancel.is_cancelled() {
+                    let mut hasher = base.clone();
+                    hasher.update(nonce.to_be_bytes());
+                    let hash = hasher.finalize();
+                    count += 1;
+                    if U256::from_be_bytes(&hash.into()) <= target {
+                        if !found.swap(true, Ordering::SeqCst) {
+                            *solution.lock().unwrap() = Some((nonce, format!("{:x}", hash)));
+                        }
+                        break;
+                    }
+                    nonce = nonce.wrapping_add(threads as u64);
+                }
+                hashes.fetch_add(count, Ordering::Relaxed);
+            });
+        }
+    });
+
+    let elapsed = start.elapsed();
+    let hashes = hashes.into_inner();
+    let (nonce, hash) = solution.into_inner().unwrap()?;
+    Some(MiningResult {
+        nonce,
+        hash,
+        threads,
+        hashes,
+        elapsed_ms: elapsed.as_millis(),
+        hashrate: hashrate(hashes, elapsed),
+    })
+}
+
+fn hashrate(hashes: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        hashes as f64 / secs
+    } else {
+        hashes as f64
+    }
+}
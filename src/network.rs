@@ -0,0 +1,144 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::amount::COIN;
+use crate::difficulty::DifficultyRules;
+use crate::genesis::GenesisConfig;
+use crate::supply::MonetaryPolicy;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    // 本地测试网络: 难度极低、不调整难度，并且可以按需立即生成区块
+    Regtest,
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(format!("Unknown network: {} (expected mainnet, testnet or regtest)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Regtest => "regtest",
+        })
+    }
+}
+
+// 一个网络的全部共识参数和默认端口
+#[derive(Serialize, Debug, Clone)]
+pub struct NetworkParams {
+    pub network: Network,
+    pub genesis: GenesisConfig,
+    pub difficulty_rules: DifficultyRules,
+    pub policy: MonetaryPolicy,
+    pub utxo: bool, // 是否使用 UTXO 交易模型
+    pub http_port: u16,
+    pub p2p_port: u16,
+    pub data_dir: String,
+}
+
+impl NetworkParams {
+    pub fn for_network(network: Network) -> Self {
+        match network {
+            Network::Mainnet => NetworkParams::mainnet(),
+            Network::Testnet => NetworkParams::testnet(),
+            Network::Regtest => NetworkParams::regtest(),
+        }
+    }
+
+    pub fn mainnet() -> Self {
+        NetworkParams {
+            network: Network::Mainnet,
+            genesis: GenesisConfig::default(),
+            difficulty_rules: DifficultyRules::default(),
+            policy: MonetaryPolicy::default(),
+            utxo: false,
+            http_port: 3000,
+            p2p_port: 4000,
+            data_dir: "data".to_string(),
+        }
+    }
+
+    pub fn testnet() -> Self {
+        NetworkParams {
+            network: Network::Testnet,
+            genesis: GenesisConfig {
+                timestamp: 1_706_745_600, // 2024-02-01 00:00:00 UTC
+                nonce: Some(2699),
+                recipient: "Creator".to_string(),
+                reward: 50 * COIN,
+                // 约等于哈希前 3 个十六进制字符为 '0'
+                bits: 0x1f0f_ffff,
+            },
+            difficulty_rules: DifficultyRules {
+                initial_bits: 0x1f0f_ffff,
+                target_block_secs: 30,
+                ..DifficultyRules::default()
+            },
+            policy: MonetaryPolicy::default(),
+            utxo: false,
+            http_port: 13000,
+            p2p_port: 14000,
+            data_dir: "data/testnet".to_string(),
+        }
+    }
+
+    pub fn regtest() -> Self {
+        NetworkParams {
+            network: Network::Regtest,
+            genesis: GenesisConfig {
+                timestamp: 1_704_067_200,
+                nonce: Some(2),
+                recipient: "Creator".to_string(),
+                reward: 50 * COIN,
+                bits: 0x207f_ffff,
+            },
+            difficulty_rules: DifficultyRules {
+                initial_bits: 0x207f_ffff,
+                retarget: false,
+                ..DifficultyRules::default()
+            },
+            policy: MonetaryPolicy {
+                halving_interval: 150,
+                ..MonetaryPolicy::default()
+            },
+            utxo: false,
+            http_port: 23000,
+            p2p_port: 24000,
+            data_dir: "data/regtest".to_string(),
+        }
+    }
+
+    // 从命令行参数中读取 --network <name> 或 --network=<name>，默认为 mainnet
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut network = Network::Mainnet;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let value = if arg == "--network" {
+                args.next().ok_or("--network requires a value")?
+            } else if let Some(value) = arg.strip_prefix("--network=") {
+                value.to_string()
+            } else {
+                return Err(format!("Unknown argument: {}", arg));
+            };
+            network = value.parse()?;
+        }
+        Ok(NetworkParams::for_network(network))
+    }
+}
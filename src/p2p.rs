@@ -0,0 +1,561 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use crate::encoding::{Decode, Encode, Reader, Writer};
+use crate::fork::BlockStatus;
+use crate::sync::{HeadersStatus, MAX_HEADERS};
+use crate::{AppState, Block, BlockHeader, Transaction};
+
+// 节点间的 TCP 协议
+//
+// 每条消息为 [u32 长度][消息编码]，消息编码的第一个字节是类型，其余字段使用规范二进制编码
+// 连接建立后双方先发送 Version，收到对方的 Version 后回复 Verack，握手完成后才处理其他消息
+pub const PROTOCOL_VERSION: u32 = 4;
+
+const MAX_MESSAGE_BYTES: usize = 32 * 1024 * 1024;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+// 定期重新分配超时的区块下载
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvKind {
+    Transaction,
+    Block,
+}
+
+// 库存条目: 通告或请求某个交易/区块
+#[derive(Debug, Clone)]
+pub struct InvItem {
+    pub kind: InvKind,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Version { version: u32, genesis_hash: String, best_height: u32, listen_port: u16 },
+    Verack,
+    Inv(Vec<InvItem>),
+    GetData(Vec<InvItem>),
+    Tx(Transaction),
+    Block(Box<Block>),
+    // 请求定位点之后的主链区块头，定位点为从链尾向创世区块稀疏取样的哈希
+    GetHeaders(Vec<String>),
+    Headers(Vec<BlockHeader>),
+}
+
+impl Encode for InvItem {
+    fn encode(&self, w: &mut Writer) {
+        w.put_u8(match self.kind {
+            InvKind::Transaction => 1,
+            InvKind::Block => 2,
+        });
+        w.put_str(&self.hash);
+    }
+}
+
+impl Decode for InvItem {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str> {
+        let kind = match r.get_u8()? {
+            1 => InvKind::Transaction,
+            2 => InvKind::Block,
+            _ => return Err("Invalid inventory kind"),
+        };
+        Ok(InvItem { kind, hash: r.get_string()? })
+    }
+}
+
+impl Encode for Message {
+    fn encode(&self, w: &mut Writer) {
+        match self {
+            Message::Version { version, genesis_hash, best_height, listen_port } => {
+                w.put_u8(0);
+                w.put_u32(*version);
+                w.put_str(genesis_hash);
+                w.put_u32(*best_height);
+                w.put_u32(*listen_port as u32);
+            }
+            Message::Verack => w.put_u8(1),
+            Message::Inv(items) => {
+                w.put_u8(2);
+                w.put_list(items);
+            }
+            Message::GetData(items) => {
+                w.put_u8(3);
+                w.put_list(items);
+            }
+            Message::Tx(tx) => {
+                w.put_u8(4);
+                tx.encode(w);
+            }
+            Message::Block(block) => {
+                w.put_u8(5);
+                block.encode(w);
+            }
+            Message::GetHeaders(locator) => {
+                w.put_u8(6);
+                w.put_list(locator);
+            }
+            Message::Headers(headers) => {
+                w.put_u8(7);
+                w.put_list(headers);
+            }
+        }
+    }
+}
+
+impl Decode for Message {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str> {
+        Ok(match r.get_u8()? {
+            0 => Message::Version {
+                version: r.get_u32()?,
+                genesis_hash: r.get_string()?,
+                best_height: r.get_u32()?,
+                listen_port: u16::try_from(r.get_u32()?).map_err(|_| "Invalid port")?,
+            },
+            1 => Message::Verack,
+            2 => Message::Inv(r.get_list()?),
+            3 => Message::GetData(r.get_list()?),
+            4 => Message::Tx(Transaction::decode(r)?),
+            5 => Message::Block(Box::new(Block::decode(r)?)),
+            6 => Message::GetHeaders(r.get_list()?),
+            7 => Message::Headers(r.get_list()?),
+            _ => return Err("Unknown message type"),
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PeerInfo {
+    pub addr: String,
+    pub outbound: bool,
+    pub version: Option<u32>,
+    pub best_height: Option<u32>,
+    pub listen_port: Option<u16>,
+}
+
+struct PeerHandle {
+    sender: mpsc::UnboundedSender<Message>,
+    info: PeerInfo,
+}
+
+// 当前连接的所有节点
+#[derive(Default)]
+pub struct Peers {
+    inner: Mutex<HashMap<SocketAddr, PeerHandle>>,
+}
+
+impl Peers {
+    pub fn list(&self) -> Vec<PeerInfo> {
+        self.inner.lock().unwrap().values().map(|peer| peer.info.clone()).collect()
+    }
+
+    // 向所有已连接的节点（except 除外）发送消息
+    pub fn broadcast(&self, message: &Message, except: Option<SocketAddr>) {
+        for (addr, peer) in self.inner.lock().unwrap().iter() {
+            if Some(*addr) != except {
+                let _ = peer.sender.send(message.clone());
+            }
+        }
+    }
+
+    pub fn announce_block(&self, hash: &str, except: Option<SocketAddr>) {
+        let item = InvItem { kind: InvKind::Block, hash: hash.to_string() };
+        self.broadcast(&Message::Inv(vec![item]), except);
+    }
+
+    pub fn announce_transaction(&self, hash: &str, except: Option<SocketAddr>) {
+        let item = InvItem { kind: InvKind::Transaction, hash: hash.to_string() };
+        self.broadcast(&Message::Inv(vec![item]), except);
+    }
+
+    pub fn send_to(&self, addr: SocketAddr, message: Message) {
+        if let Some(peer) = self.inner.lock().unwrap().get(&addr) {
+            let _ = peer.sender.send(message);
+        }
+    }
+
+    // 已收到 Version 的节点，我们的 Verack 已在发送队列中，可以向其请求数据
+    fn ready(&self) -> Vec<SocketAddr> {
+        self.inner
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, peer)| peer.info.version.is_some())
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    fn register(&self, addr: SocketAddr, sender: mpsc::UnboundedSender<Message>, outbound: bool) {
+        let info = PeerInfo {
+            addr: addr.to_string(),
+            outbound,
+            version: None,
+            best_height: None,
+            listen_port: None,
+        };
+        self.inner.lock().unwrap().insert(addr, PeerHandle { sender, info });
+    }
+
+    fn update(&self, addr: SocketAddr, f: impl FnOnce(&mut PeerInfo)) {
+        if let Some(peer) = self.inner.lock().unwrap().get_mut(&addr) {
+            f(&mut peer.info);
+        }
+    }
+
+    fn unregister(&self, addr: SocketAddr) {
+        self.inner.lock().unwrap().remove(&addr);
+    }
+}
+
+// 接受其他节点的连接
+pub async fn listen(state: Arc<AppState>, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    println!("P2P listening on 127.0.0.1:{}", port);
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = run_session(state, stream, addr, false).await {
+                println!("Peer {} disconnected: {}", addr, e);
+            }
+        });
+    }
+}
+
+// 主动连接一个节点，断开后定期重连
+pub async fn connect(state: Arc<AppState>, peer: String) {
+    loop {
+        match TcpStream::connect(&peer).await {
+            Ok(stream) => {
+                let addr = stream.peer_addr().unwrap_or_else(|_| ([0, 0, 0, 0], 0).into());
+                println!("Connected to peer {}", peer);
+                if let Err(e) = run_session(state.clone(), stream, addr, true).await {
+                    println!("Peer {} disconnected: {}", peer, e);
+                }
+            }
+            Err(e) => println!("Failed to connect to peer {}: {}", peer, e),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn run_session(
+    state: Arc<AppState>,
+    stream: TcpStream,
+    addr: SocketAddr,
+    outbound: bool,
+) -> io::Result<()> {
+    let (mut reader, mut writer) = stream.into_split();
+    let (sender, mut outgoing) = mpsc::unbounded_channel::<Message>();
+    state.peers.register(addr, sender.clone(), outbound);
+
+    let writer_task = tokio::spawn(async move {
+        while let Some(message) = outgoing.recv().await {
+            if write_message(&mut writer, &message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let _ = sender.send(version_message(&state));
+    let mut session = Session {
+        state: &state,
+        addr,
+        sender: &sender,
+        handshake_done: false,
+    };
+    let result = async {
+        loop {
+            let message = read_message(&mut reader).await?;
+            session.handle(message)?;
+        }
+    }
+    .await;
+
+    state.peers.unregister(addr);
+    writer_task.abort();
+    // 把该节点未完成的区块下载分配给其他节点
+    schedule_downloads(&state);
+    result
+}
+
+// 定期检查区块下载是否超时
+pub async fn run_sync(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(SYNC_INTERVAL);
+    loop {
+        interval.tick().await;
+        schedule_downloads(&state);
+    }
+}
+
+fn schedule_downloads(state: &AppState) {
+    let peers = state.peers.ready();
+    let requests = {
+        let bc = state.blockchain.read().unwrap();
+        state.sync.lock().unwrap().schedule(&bc, &peers, Instant::now())
+    };
+    for (addr, hashes) in requests {
+        let items = hashes
+            .into_iter()
+            .map(|hash| InvItem { kind: InvKind::Block, hash })
+            .collect();
+        state.peers.send_to(addr, Message::GetData(items));
+    }
+}
+
+// 按区块头顺序接入已下载的区块
+fn connect_downloaded(state: &AppState) {
+    let mut connected = None;
+    {
+        let mut bc = state.blockchain.write().unwrap();
+        let mut sync = state.sync.lock().unwrap();
+        for block in sync.take_connectable(&bc) {
+            let hash = block.hash.clone();
+            if let Err(e) = bc.submit_block(block) {
+                // 下载时已确认区块与区块头一致，仍未通过完整校验说明区块头链本身无效，放弃整条区块头链
+                println!("Sync block {} rejected: {}", hash, e);
+                sync.reset();
+                break;
+            }
+            connected = Some(hash);
+        }
+    }
+    // 同步过程中只通告最新接入的区块
+    if let Some(hash) = connected {
+        state.restart_mining();
+        state.peers.announce_block(&hash, None);
+    }
+}
+
+async fn read_message(reader: &mut (impl AsyncReadExt + Unpin)) -> io::Result<Message> {
+    let len = reader.read_u32().await? as usize;
+    if len > MAX_MESSAGE_BYTES {
+        return Err(invalid_data("Message too large"));
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    Message::from_bytes(&payload).map_err(invalid_data)
+}
+
+async fn write_message(writer: &mut OwnedWriteHalf, message: &Message) -> io::Result<()> {
+    let payload = message.to_bytes();
+    writer.write_u32(payload.len() as u32).await?;
+    writer.write_all(&payload).await
+}
+
+fn invalid_data(e: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn version_message(state: &AppState) -> Message {
+    let bc = state.blockchain.read().unwrap();
+    Message::Version {
+        version: PROTOCOL_VERSION,
+        genesis_hash: bc.genesis_hash().to_string(),
+        best_height: bc.chain.len() as u32 - 1,
+        listen_port: state.params.p2p_port,
+    }
+}
+
+// 从链尾开始，前 10 个区块逐个取样，之后步长加倍，最后总是包含创世区块
+fn locator(chain: &[Block]) -> Vec<String> {
+    let mut hashes = Vec::new();
+    let mut height = chain.len() - 1;
+    let mut step = 1;
+    loop {
+        hashes.push(chain[height].hash.clone());
+        if height == 0 {
+            break;
+        }
+        if hashes.len() >= 10 {
+            step *= 2;
+        }
+        height = height.saturating_sub(step);
+    }
+    hashes
+}
+
+struct Session<'a> {
+    state: &'a AppState,
+    addr: SocketAddr,
+    sender: &'a mpsc::UnboundedSender<Message>,
+    handshake_done: bool,
+}
+
+impl Session<'_> {
+    fn send(&self, message: Message) {
+        let _ = self.sender.send(message);
+    }
+
+    // 请求主链（以及正在同步的区块头链）之后的区块头
+    fn request_headers(&self) {
+        let bc = self.state.blockchain.read().unwrap();
+        let mut hashes: Vec<String> = self.state.sync.lock().unwrap().tip_hash().cloned().into_iter().collect();
+        hashes.extend(locator(&bc.chain));
+        self.send(Message::GetHeaders(hashes));
+    }
+
+    fn handle(&mut self, message: Message) -> io::Result<()> {
+        match message {
+            Message::Version { version, genesis_hash, best_height, listen_port } => {
+                if version != PROTOCOL_VERSION {
+                    return Err(invalid_data("Unsupported protocol version"));
+                }
+                if genesis_hash != self.state.blockchain.read().unwrap().genesis_hash() {
+                    return Err(invalid_data("Peer uses a different genesis block"));
+                }
+                self.state.peers.update(self.addr, |info| {
+                    info.version = Some(version);
+                    info.best_height = Some(best_height);
+                    info.listen_port = Some(listen_port);
+                });
+                self.send(Message::Verack);
+            }
+            Message::Verack => {
+                self.handshake_done = true;
+                // 握手完成后先同步对方的区块头
+                self.request_headers();
+            }
+            _ if !self.handshake_done => return Err(invalid_data("Message before handshake")),
+            Message::Inv(items) => self.handle_inv(items),
+            Message::GetData(items) => self.handle_get_data(items),
+            Message::Tx(tx) => self.handle_tx(tx),
+            Message::Block(block) => self.handle_block(*block)?,
+            Message::GetHeaders(locator) => self.handle_get_headers(locator),
+            Message::Headers(headers) => self.handle_headers(headers)?,
+        }
+        Ok(())
+    }
+
+    fn handle_inv(&self, items: Vec<InvItem>) {
+        let (unknown_block, wanted) = {
+            let bc = self.state.blockchain.read().unwrap();
+            let unknown_block = items
+                .iter()
+                .any(|item| item.kind == InvKind::Block && !bc.contains_block(&item.hash));
+            let wanted: Vec<InvItem> = items
+                .into_iter()
+                .filter(|item| item.kind == InvKind::Transaction && !bc.mempool.contains(&item.hash))
+                .collect();
+            (unknown_block, wanted)
+        };
+        if !wanted.is_empty() {
+            self.send(Message::GetData(wanted));
+        }
+        // 新区块也通过区块头同步获取
+        if unknown_block {
+            self.request_headers();
+        }
+    }
+
+    fn handle_get_data(&self, items: Vec<InvItem>) {
+        let bc = self.state.blockchain.read().unwrap();
+        for item in items {
+            match item.kind {
+                InvKind::Block => {
+                    if let Some(block) = bc.get_block(&item.hash) {
+                        self.send(Message::Block(Box::new(block.clone())));
+                    }
+                }
+                InvKind::Transaction => {
+                    if let Some(tx) = bc.mempool.get(&item.hash) {
+                        self.send(Message::Tx(tx.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_tx(&self, tx: Transaction) {
+        let hash = tx.hash();
+        let result = self.state.blockchain.write().unwrap().add_transaction(tx.clone());
+        if result.is_ok() {
+            self.state.transaction_accepted(&tx);
+            self.state.peers.announce_transaction(&hash, Some(self.addr));
+        }
+    }
+
+    fn handle_block(&self, block: Block) -> io::Result<()> {
+        let hash = block.hash.clone();
+        let received = self.state.sync.lock().unwrap().block_received(self.addr, block);
+        // 请求的区块与区块头不符说明对方不可信，断开连接；区块头链保持不变
+        let received = received.map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Block {} does not match its header: {}", hash, e))
+        })?;
+        let Some(block) = received else {
+            connect_downloaded(self.state);
+            schedule_downloads(self.state);
+            return Ok(());
+        };
+        let result = {
+            let mut bc = self.state.blockchain.write().unwrap();
+            if bc.contains_block(&hash) {
+                return Ok(());
+            }
+            if !bc.contains_block(&block.header.previous_hash) {
+                None
+            } else {
+                Some(bc.submit_block(block))
+            }
+        };
+        match result {
+            // 父区块未知，说明我们落后了，请求缺少的区块
+            None => self.request_headers(),
+            Some(Ok(status)) => {
+                if status != BlockStatus::SideChain {
+                    self.state.restart_mining();
+                }
+                self.state.peers.announce_block(&hash, Some(self.addr));
+            }
+            Some(Err(e)) => println!("Rejected block {} from {}: {}", hash, self.addr, e),
+        }
+        Ok(())
+    }
+
+    fn handle_get_headers(&self, locator: Vec<String>) {
+        let bc = self.state.blockchain.read().unwrap();
+        // 找到定位点中第一个位于我们主链上的区块；都不认识时说明创世区块不同
+        let Some(fork_height) = locator.iter().find_map(|hash| bc.height_of(hash)) else {
+            return;
+        };
+        let headers: Vec<BlockHeader> = bc.chain[fork_height + 1..]
+            .iter()
+            .take(MAX_HEADERS)
+            .map(|block| block.header.clone())
+            .collect();
+        self.send(Message::Headers(headers));
+    }
+
+    fn handle_headers(&self, headers: Vec<BlockHeader>) -> io::Result<()> {
+        let full_batch = headers.len() >= MAX_HEADERS;
+        let status = {
+            let bc = self.state.blockchain.read().unwrap();
+            self.state.sync.lock().unwrap().accept_headers(&bc, headers)
+        };
+        // 无效的区块头说明对方不可信，断开连接
+        let status = status.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        match status {
+            HeadersStatus::Extended => {
+                schedule_downloads(self.state);
+                // 对方还有更多区块头，以新的区块头链尾为起点继续请求
+                if full_batch {
+                    self.request_headers();
+                }
+            }
+            // 同步已被重置，定位点中不再包含旧的区块头链尾，从主链重新请求
+            HeadersStatus::Unconnected => self.request_headers(),
+            HeadersStatus::NotBetter => {}
+        }
+        Ok(())
+    }
+}
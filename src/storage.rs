@@ -0,0 +1,284 @@
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::encoding::{Decode, Encode};
+use crate::mempool::SavedTransaction;
+use crate::Block;
+
+// 区块与内存池的持久化接口
+pub trait BlockStore: Debug + Send + Sync {
+    // 启动时按写入顺序读取已保存的全部区块（包括侧链），父区块总在子区块之前
+    fn load_blocks(&mut self) -> io::Result<Vec<Block>>;
+    // 追加一个新区块，返回成功时数据已落盘
+    fn append_block(&mut self, block: &Block) -> io::Result<()>;
+    fn load_mempool(&mut self) -> io::Result<Vec<SavedTransaction>>;
+    fn save_mempool(&mut self, transactions: &[SavedTransaction]) -> io::Result<()>;
+}
+
+// 不做任何持久化，重启后数据丢失
+#[derive(Debug, Default)]
+pub struct MemoryStore;
+
+impl BlockStore for MemoryStore {
+    fn load_blocks(&mut self) -> io::Result<Vec<Block>> {
+        Ok(Vec::new())
+    }
+
+    fn append_block(&mut self, _block: &Block) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn load_mempool(&mut self) -> io::Result<Vec<SavedTransaction>> {
+        Ok(Vec::new())
+    }
+
+    fn save_mempool(&mut self, _transactions: &[SavedTransaction]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// 基于目录的存储:
+// - blocks.dat: [4 字节魔数][u32 格式版本]，之后是追加写入的区块记录，每条为 [u32 长度][32 字节 SHA-256 校验和][区块编码]
+// - blocks.idx: 每个区块在 blocks.dat 中的偏移量 (u64 大端序)
+// - mempool.dat: 内存池快照（每笔交易及其进入内存池的时间），整体写入临时文件后再重命名替换
+#[derive(Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+const RECORD_HEADER_LEN: usize = 4 + 32;
+
+// 区块编码变化时递增格式版本，旧版本的数据目录不会被误读或截断
+const DATA_MAGIC: &[u8; 4] = b"LBCK";
+const DATA_VERSION: u32 = 1;
+const DATA_HEADER_LEN: usize = 4 + 4;
+
+fn data_header() -> Vec<u8> {
+    let mut header = DATA_MAGIC.to_vec();
+    header.extend_from_slice(&DATA_VERSION.to_be_bytes());
+    header
+}
+
+impl FileStore {
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(FileStore { dir })
+    }
+
+    fn data_path(&self) -> PathBuf {
+        self.dir.join("blocks.dat")
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join("blocks.idx")
+    }
+
+    fn mempool_path(&self) -> PathBuf {
+        self.dir.join("mempool.dat")
+    }
+
+    // 写入临时文件后重命名，保证读者只会看到完整的旧文件或新文件
+    fn replace_file(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+}
+
+// 解析一条区块记录，返回区块与记录占用的字节数
+// 记录超出文件末尾（最后一次写入被中断）时返回 None，校验和或编码错误时返回 InvalidData
+fn decode_record(data: &[u8]) -> io::Result<Option<(Block, usize)>> {
+    if data.len() < RECORD_HEADER_LEN {
+        return Ok(None);
+    }
+    let len = u32::from_be_bytes(data[..4].try_into().unwrap()) as usize;
+    let checksum = &data[4..RECORD_HEADER_LEN];
+    let Some(payload) = data.get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + len) else {
+        return Ok(None);
+    };
+    if Sha256::digest(payload).as_slice() != checksum {
+        return Err(invalid_data("block record checksum mismatch"));
+    }
+    let block = Block::from_bytes(payload).map_err(invalid_data)?;
+    Ok(Some((block, RECORD_HEADER_LEN + len)))
+}
+
+fn invalid_data(e: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn read_or_empty(path: &Path) -> io::Result<Vec<u8>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(bytes),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+impl BlockStore for FileStore {
+    fn load_blocks(&mut self) -> io::Result<Vec<Block>> {
+        let data = read_or_empty(&self.data_path())?;
+
+        // 文件头不完整只可能是第一次写入被中断，此时还没有任何区块
+        let header = data_header();
+        let mut pos = if data.len() < DATA_HEADER_LEN && header.starts_with(&data) {
+            0
+        } else if data.starts_with(&header) {
+            DATA_HEADER_LEN
+        } else {
+            return Err(invalid_data("blocks.dat has an unknown format version"));
+        };
+
+        let mut blocks = Vec::new();
+        let mut offsets = Vec::new();
+        while pos < data.len() {
+            let Some((block, used)) = decode_record(&data[pos..])? else {
+                break;
+            };
+            offsets.push(pos as u64);
+            blocks.push(block);
+            pos += used;
+        }
+
+        // 最后一次写入被中断时，丢弃不完整的尾部记录
+        if pos < data.len() {
+            eprintln!(
+                "Discarding {} bytes of incomplete block data after block {}",
+                data.len() - pos,
+                blocks.len()
+            );
+            let file = OpenOptions::new().write(true).open(self.data_path())?;
+            file.set_len(pos as u64)?;
+            file.sync_all()?;
+        }
+
+        // 索引可以由数据文件重建，不一致时直接重写
+        let index: Vec<u8> = offsets.iter().flat_map(|o| o.to_be_bytes()).collect();
+        if read_or_empty(&self.index_path())? != index {
+            self.replace_file(&self.index_path(), &index)?;
+        }
+
+        Ok(blocks)
+    }
+
+    fn append_block(&mut self, block: &Block) -> io::Result<()> {
+        let payload = block.to_bytes();
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+        record.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        record.extend_from_slice(&Sha256::digest(&payload));
+        record.extend_from_slice(&payload);
+
+        let mut data = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.data_path())?;
+        let mut offset = data.metadata()?.len();
+        // 新文件先写入文件头
+        if offset == 0 {
+            record.splice(..0, data_header());
+        }
+        if let Err(e) = data.write_all(&record).and_then(|_| data.sync_data()) {
+            // 尽量回滚到写入前的长度，失败也会在下次启动时被截断
+            let _ = data.set_len(offset);
+            return Err(e);
+        }
+
+        if offset == 0 {
+            offset = DATA_HEADER_LEN as u64;
+        }
+        let mut index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.index_path())?;
+        index.write_all(&offset.to_be_bytes())?;
+        index.sync_data()
+    }
+
+    fn load_mempool(&mut self) -> io::Result<Vec<SavedTransaction>> {
+        let bytes = read_or_empty(&self.mempool_path())?;
+        if bytes.is_empty() {
+            return Ok(Vec::new());
+        }
+        Vec::<SavedTransaction>::from_bytes(&bytes).map_err(invalid_data)
+    }
+
+    fn save_mempool(&mut self, transactions: &[SavedTransaction]) -> io::Result<()> {
+        self.replace_file(&self.mempool_path(), &transactions.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transaction;
+
+    // 每个测试使用独立的临时目录
+    fn temp_store(name: &str) -> (FileStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("learn_blockchain-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        (FileStore::open(&dir).unwrap(), dir)
+    }
+
+    fn store_with_blocks(name: &str, count: u32) -> (FileStore, PathBuf) {
+        let (mut store, dir) = temp_store(name);
+        for height in 0..count {
+            let coinbase = Transaction::coinbase("miner", 50, height);
+            let block = Block::new(height, 0x207f_ffff, vec![coinbase], "0".to_string());
+            store.append_block(&block).unwrap();
+        }
+        (store, dir)
+    }
+
+    fn hashes(blocks: &[Block]) -> Vec<String> {
+        blocks.iter().map(|block| block.hash.clone()).collect()
+    }
+
+    #[test]
+    fn truncated_last_record_is_discarded() {
+        let (mut store, dir) = store_with_blocks("truncated", 3);
+        let blocks = store.load_blocks().unwrap();
+        let data = fs::read(store.data_path()).unwrap();
+        fs::write(store.data_path(), &data[..data.len() - 5]).unwrap();
+
+        let recovered = store.load_blocks().unwrap();
+        assert_eq!(hashes(&recovered), hashes(&blocks[..2]));
+        // 截断后可以继续追加
+        store.append_block(&blocks[2]).unwrap();
+        assert_eq!(hashes(&store.load_blocks().unwrap()), hashes(&blocks));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_record_is_an_error_and_keeps_the_file() {
+        let (mut store, dir) = store_with_blocks("corrupt", 3);
+        let mut data = fs::read(store.data_path()).unwrap();
+        // 第一个区块记录的载荷中的一个字节
+        data[DATA_HEADER_LEN + RECORD_HEADER_LEN + 1] ^= 0xff;
+        fs::write(store.data_path(), &data).unwrap();
+
+        let err = store.load_blocks().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read(store.data_path()).unwrap(), data);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_format_version_is_rejected() {
+        let (mut store, dir) = store_with_blocks("version", 1);
+        let mut data = fs::read(store.data_path()).unwrap();
+        data[DATA_HEADER_LEN - 1] = 0;
+        fs::write(store.data_path(), &data).unwrap();
+
+        let err = store.load_blocks().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read(store.data_path()).unwrap().len(), data.len());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use serde::Serialize;
+
+use crate::amount::{self, COIN};
+use crate::ledger::Ledger;
+use crate::Block;
+
+// 货币发行规则: 区块奖励每 halving_interval 个区块减半，总发行量不超过 max_supply
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct MonetaryPolicy {
+    #[serde(with = "amount::decimal")]
+    pub initial_subsidy: u64,
+    pub halving_interval: u32,
+    #[serde(with = "amount::decimal")]
+    pub max_supply: u64,
+}
+
+impl Default for MonetaryPolicy {
+    fn default() -> Self {
+        MonetaryPolicy {
+            initial_subsidy: 50 * COIN,
+            halving_interval: 210_000,
+            max_supply: 21_000_000 * COIN,
+        }
+    }
+}
+
+impl MonetaryPolicy {
+    // 高度 height 的区块奖励（不含手续费）
+    pub fn subsidy_at(&self, height: u32) -> u64 {
+        let halvings = height / self.halving_interval.max(1);
+        if halvings >= 64 {
+            return 0;
+        }
+        self.initial_subsidy >> halvings
+    }
+
+    // 从创世区块到 height（含）按规则应发行的总量
+    // 创世区块的奖励由创世配置固定，其余区块按减半规则计算
+    pub fn expected_issuance(&self, height: u32, genesis_reward: u64) -> u64 {
+        let interval = self.halving_interval.max(1) as u64;
+        let blocks = height as u64 + 1;
+        let mut total: u64 = 0;
+        let mut era = 0u64;
+        // 每个减半周期内奖励相同，按周期累加即可
+        while era * interval < blocks && era < 64 {
+            let era_blocks = (blocks - era * interval).min(interval);
+            let subsidy = self.initial_subsidy >> era;
+            total = total.saturating_add(subsidy.saturating_mul(era_blocks));
+            era += 1;
+        }
+        total
+            .saturating_sub(self.initial_subsidy)
+            .saturating_add(genesis_reward)
+            .min(self.max_supply)
+    }
+}
+
+// 发行量审计结果
+#[derive(Serialize, Debug)]
+pub struct SupplyReport {
+    pub height: u32,
+    // 链上 coinbase 实际发行的总量（手续费只是转移，不计入发行）
+    #[serde(with = "amount::decimal")]
+    pub circulating: u64,
+    // 所有账户余额之和，应与 circulating 相等
+    #[serde(with = "amount::decimal")]
+    pub sum_of_balances: u64,
+    // 按发行规则到当前高度最多应发行的总量
+    #[serde(with = "amount::decimal")]
+    pub expected_issuance: u64,
+    #[serde(with = "amount::decimal")]
+    pub max_supply: u64,
+    pub consistent: bool,
+}
+
+pub fn audit(chain: &[Block], policy: &MonetaryPolicy) -> SupplyReport {
+    let height = chain.len().saturating_sub(1) as u32;
+    let ledger = Ledger::from_chain(chain);
+    let circulating = ledger.issued();
+    let sum_of_balances = ledger.total_balance();
+    let genesis_reward = chain
+        .first()
+        .and_then(|genesis| genesis.transactions.first())
+        .map_or(0, |coinbase| coinbase.amount);
+    let expected_issuance = policy.expected_issuance(height, genesis_reward);
+    SupplyReport {
+        height,
+        circulating,
+        sum_of_balances,
+        expected_issuance,
+        max_supply: policy.max_supply,
+        consistent: circulating == sum_of_balances
+            && circulating <= expected_issuance
+            && circulating <= policy.max_supply,
+    }
+}
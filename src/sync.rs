@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::difficulty;
+use crate::target::U256;
+use crate::{Block, BlockError, BlockHeader, Blockchain};
+
+// 先下载区块头的同步 (headers-first)
+//
+// 1. 向节点发送 GetHeaders，收到的区块头只做链接、难度和工作量证明检查，组成最佳区块头链
+// 2. 按区块头顺序把区块体分配给多个节点并行下载，每个节点同时最多请求 MAX_BLOCKS_PER_PEER 个
+// 3. 下载完成的区块按顺序接入区块链，在这一步做完整校验
+
+// 单条 Headers 消息最多携带的区块头数量
+pub const MAX_HEADERS: usize = 2000;
+const MAX_BLOCKS_PER_PEER: usize = 16;
+// 只下载最佳区块头链上前 DOWNLOAD_WINDOW 个尚未接入的区块，限制缓存的区块数量
+const DOWNLOAD_WINDOW: usize = 1024;
+// 超时未收到的区块会重新分配给其他节点
+const BLOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default)]
+pub struct HeaderSync {
+    // 最佳区块头链与主链的分叉点高度，headers 接在这个高度之后
+    fork_height: usize,
+    headers: Vec<BlockHeader>,
+    hashes: Vec<String>,
+    work: U256,
+    in_flight: HashMap<String, (SocketAddr, Instant)>,
+    downloaded: HashMap<String, Block>,
+}
+
+// 处理一批区块头的结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeadersStatus {
+    // 区块头链变长，调用方据此继续请求后续区块头
+    Extended,
+    // 合法但工作量不超过已知的最佳链
+    NotBetter,
+    // 接不上已知的区块头或主链，例如请求发出后同步被重置，应改用主链的定位点重新请求
+    Unconnected,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SyncProgress {
+    pub syncing: bool,
+    pub block_height: usize,
+    pub header_height: usize,
+    pub blocks_in_flight: usize,
+    pub blocks_downloaded: usize,
+    pub percent: f64,
+}
+
+impl HeaderSync {
+    // 校验并合并收到的区块头；只有区块头本身不合法时返回错误
+    pub fn accept_headers(&mut self, bc: &Blockchain, headers: Vec<BlockHeader>) -> Result<HeadersStatus, String> {
+        self.prune(bc);
+        let Some(first) = headers.first() else {
+            return Ok(HeadersStatus::NotBetter);
+        };
+
+        // 新区块头要么接在已知的区块头之后，要么接在主链上的某个区块之后
+        let (fork_height, mut candidate) = match self.hashes.iter().position(|h| *h == first.previous_hash) {
+            Some(i) => (self.fork_height, self.headers[..=i].to_vec()),
+            None => match bc.height_of(&first.previous_hash) {
+                Some(height) => (height, Vec::new()),
+                None => return Ok(HeadersStatus::Unconnected),
+            },
+        };
+        let mut chain: Vec<&BlockHeader> = bc.chain[..=fork_height].iter().map(|b| &b.header).collect();
+        chain.extend(candidate.iter());
+
+        let mut work = chain_work_at(bc, fork_height, &candidate);
+        let mut validated = Vec::with_capacity(headers.len());
+        for header in &headers {
+            header
+                .validate(
+                    chain.last().copied(),
+                    bc.difficulty_rules.next_bits(&chain),
+                    difficulty::median_time_past(&chain),
+                )
+                .map_err(|e| e.to_string())?;
+            work = work.saturating_add(&header.work());
+            chain.push(header);
+            validated.push(header.clone());
+        }
+        candidate.extend(validated);
+
+        if work <= self.best_work(bc) {
+            return Ok(HeadersStatus::NotBetter);
+        }
+        self.fork_height = fork_height;
+        self.hashes = candidate.iter().map(|h| h.calculate_hash()).collect();
+        self.headers = candidate;
+        self.work = work;
+        self.prune(bc);
+        Ok(HeadersStatus::Extended)
+    }
+
+    // 区块头链和已知最佳链中较大的累计工作量
+    fn best_work(&self, bc: &Blockchain) -> U256 {
+        if self.headers.is_empty() {
+            bc.total_work()
+        } else {
+            self.work.max(bc.total_work())
+        }
+    }
+
+    // 去掉已经接入主链的区块头；主链的工作量不低于区块头链时同步结束
+    fn prune(&mut self, bc: &Blockchain) {
+        let connected = self
+            .hashes
+            .iter()
+            .take_while(|hash| bc.height_of(hash).is_some())
+            .count();
+        if connected > 0 {
+            self.fork_height += connected;
+            self.headers.drain(..connected);
+            self.hashes.drain(..connected);
+        }
+        // 主链发生重组后分叉点可能已不在主链上
+        let detached = bc
+            .chain
+            .get(self.fork_height)
+            .zip(self.headers.first())
+            .is_none_or(|(block, header)| block.hash != header.previous_hash);
+        if detached || bc.total_work() >= self.work {
+            self.reset();
+        }
+    }
+
+    // 放弃当前的区块头链，例如其中的区块未通过完整校验
+    pub fn reset(&mut self) {
+        *self = HeaderSync::default();
+    }
+
+    pub fn is_syncing(&self) -> bool {
+        !self.headers.is_empty()
+    }
+
+    // 最佳区块头链的链尾，请求后续区块头时放在定位点最前面
+    pub fn tip_hash(&self) -> Option<&String> {
+        self.hashes.last()
+    }
+
+    // 为每个节点分配要下载的区块，返回 (节点, 区块哈希) 列表
+    pub fn schedule(&mut self, bc: &Blockchain, peers: &[SocketAddr], now: Instant) -> Vec<(SocketAddr, Vec<String>)> {
+        self.prune(bc);
+        self.in_flight
+            .retain(|_, (addr, sent)| peers.contains(addr) && now.duration_since(*sent) < BLOCK_TIMEOUT);
+        if peers.is_empty() {
+            return Vec::new();
+        }
+
+        let mut load: HashMap<SocketAddr, usize> = peers.iter().map(|addr| (*addr, 0)).collect();
+        for (addr, _) in self.in_flight.values() {
+            *load.entry(*addr).or_default() += 1;
+        }
+        let mut requests: HashMap<SocketAddr, Vec<String>> = HashMap::new();
+        let pending = self
+            .hashes
+            .iter()
+            .filter(|hash| !bc.contains_block(hash))
+            .take(DOWNLOAD_WINDOW)
+            .filter(|hash| !self.downloaded.contains_key(*hash) && !self.in_flight.contains_key(*hash));
+        for hash in pending {
+            let Some((addr, count)) = load.iter_mut().min_by_key(|(_, count)| **count) else {
+                break;
+            };
+            if *count >= MAX_BLOCKS_PER_PEER {
+                break;
+            }
+            *count += 1;
+            requests.entry(*addr).or_default().push(hash.clone());
+        }
+        for (addr, hashes) in &requests {
+            for hash in hashes {
+                self.in_flight.insert(hash.clone(), (*addr, now));
+            }
+        }
+        requests.into_iter().collect()
+    }
+
+    // 缓存 addr 发来的同步请求的区块；不是向它请求的区块原样返回，由调用方按普通广播处理
+    // 区块内容与所请求的区块头不符时丢弃该区块并返回错误，调用方应断开对方，区块稍后改向其他节点请求
+    pub fn block_received(&mut self, addr: SocketAddr, block: Block) -> Result<Option<Block>, BlockError> {
+        if self.in_flight.get(&block.hash).is_none_or(|(peer, _)| *peer != addr) {
+            return Ok(Some(block));
+        }
+        self.in_flight.remove(&block.hash);
+        // 请求的哈希来自已校验的区块头链，哈希一致即区块头就是所请求的区块头
+        block.check_commitments()?;
+        self.downloaded.insert(block.hash.clone(), block);
+        Ok(None)
+    }
+
+    // 取出按区块头顺序可以依次接入的已下载区块
+    pub fn take_connectable(&mut self, bc: &Blockchain) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        for hash in &self.hashes {
+            if bc.contains_block(hash) {
+                continue;
+            }
+            match self.downloaded.remove(hash) {
+                Some(block) => blocks.push(block),
+                None => break,
+            }
+        }
+        blocks
+    }
+
+    pub fn progress(&self, bc: &Blockchain) -> SyncProgress {
+        let block_height = bc.chain.len() - 1;
+        let header_height = (self.fork_height + self.headers.len()).max(block_height);
+        SyncProgress {
+            syncing: self.is_syncing(),
+            block_height,
+            header_height,
+            blocks_in_flight: self.in_flight.len(),
+            blocks_downloaded: self.downloaded.len(),
+            percent: if header_height == 0 {
+                100.0
+            } else {
+                block_height as f64 * 100.0 / header_height as f64
+            },
+        }
+    }
+}
+
+// 主链前 fork_height 个区块加上 headers 的累计工作量
+fn chain_work_at(bc: &Blockchain, fork_height: usize, headers: &[BlockHeader]) -> U256 {
+    headers
+        .iter()
+        .fold(bc.chain_work[fork_height], |work, header| work.saturating_add(&header.work()))
+}
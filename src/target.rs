@@ -0,0 +1,299 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+// 256 位无符号整数，用于工作量证明目标值和累计工作量
+// 内部以 4 个 u64 小端序存储（limbs[0] 为最低位）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct U256([u64; 4]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0; 4]);
+    pub const MAX: U256 = U256([u64::MAX; 4]);
+
+    pub fn from_u64(v: u64) -> Self {
+        U256([v, 0, 0, 0])
+    }
+
+    // 把 32 字节大端序的哈希解释为整数
+    pub fn from_be_bytes(bytes: &[u8; 32]) -> Self {
+        let mut limbs = [0u64; 4];
+        for (i, chunk) in bytes.chunks_exact(8).enumerate() {
+            limbs[3 - i] = u64::from_be_bytes(chunk.try_into().unwrap());
+        }
+        U256(limbs)
+    }
+
+    // 解析 64 个字符的十六进制字符串（区块哈希的格式）
+    pub fn from_hex(s: &str) -> Option<Self> {
+        let bytes: [u8; 32] = hex::decode(s).ok()?.try_into().ok()?;
+        Some(U256::from_be_bytes(&bytes))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 4]
+    }
+
+    fn low_u64(&self) -> u64 {
+        self.0[0]
+    }
+
+    // 最高有效位的位置（0 表示值为 0）
+    pub fn bits(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                return 64 * i as u32 + (64 - self.0[i].leading_zeros());
+            }
+        }
+        0
+    }
+
+    pub fn shl(&self, shift: u32) -> Self {
+        let mut out = [0u64; 4];
+        let (limbs, bits) = ((shift / 64) as usize, shift % 64);
+        for (i, limb) in out.iter_mut().enumerate().skip(limbs) {
+            *limb = self.0[i - limbs] << bits;
+            if bits > 0 && i > limbs {
+                *limb |= self.0[i - limbs - 1] >> (64 - bits);
+            }
+        }
+        U256(out)
+    }
+
+    pub fn shr(&self, shift: u32) -> Self {
+        let mut out = [0u64; 4];
+        let (limbs, bits) = ((shift / 64) as usize, shift % 64);
+        for (i, limb) in out.iter_mut().enumerate().take(4usize.saturating_sub(limbs)) {
+            *limb = self.0[i + limbs] >> bits;
+            if bits > 0 && i + limbs + 1 < 4 {
+                *limb |= self.0[i + limbs + 1] << (64 - bits);
+            }
+        }
+        U256(out)
+    }
+
+    pub fn checked_add(&self, other: &U256) -> Option<U256> {
+        let mut out = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = c1 || c2;
+        }
+        (!carry).then_some(U256(out))
+    }
+
+    pub fn saturating_add(&self, other: &U256) -> U256 {
+        self.checked_add(other).unwrap_or(U256::MAX)
+    }
+
+    fn wrapping_sub(&self, other: &U256) -> U256 {
+        let mut out = [0u64; 4];
+        let mut borrow = false;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let (diff, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            *limb = diff;
+            borrow = b1 || b2;
+        }
+        U256(out)
+    }
+
+    pub fn checked_mul_u64(&self, v: u64) -> Option<U256> {
+        let mut out = [0u64; 4];
+        let mut carry = 0u128;
+        for (i, limb) in out.iter_mut().enumerate() {
+            let product = self.0[i] as u128 * v as u128 + carry;
+            *limb = product as u64;
+            carry = product >> 64;
+        }
+        (carry == 0).then_some(U256(out))
+    }
+
+    pub fn div_u64(&self, v: u64) -> U256 {
+        let mut out = [0u64; 4];
+        let mut rem = 0u128;
+        for i in (0..4).rev() {
+            let cur = (rem << 64) | self.0[i] as u128;
+            out[i] = (cur / v as u128) as u64;
+            rem = cur % v as u128;
+        }
+        U256(out)
+    }
+
+    // 逐位的长除法，divisor 不能为 0
+    pub fn div(&self, divisor: &U256) -> U256 {
+        let mut quotient = U256::ZERO;
+        let mut rem = U256::ZERO;
+        for bit in (0..256).rev() {
+            rem = rem.shl(1);
+            if self.bit(bit) {
+                rem.0[0] |= 1;
+            }
+            if rem >= *divisor {
+                rem = rem.wrapping_sub(divisor);
+                quotient.0[(bit / 64) as usize] |= 1 << (bit % 64);
+            }
+        }
+        quotient
+    }
+
+    fn bit(&self, n: u32) -> bool {
+        self.0[(n / 64) as usize] >> (n % 64) & 1 == 1
+    }
+
+    fn not(&self) -> U256 {
+        U256(self.0.map(|limb| !limb))
+    }
+
+    // 把紧凑格式 (nBits) 解码为目标值: 高 8 位为字节长度，低 23 位为尾数
+    // 负数或溢出 256 位的编码视为非法
+    pub fn from_compact(bits: u32) -> Option<U256> {
+        let size = bits >> 24;
+        let mantissa = bits & 0x007f_ffff;
+        if bits & 0x0080_0000 != 0 && mantissa != 0 {
+            return None;
+        }
+        let target = if size <= 3 {
+            U256::from_u64((mantissa >> (8 * (3 - size))) as u64)
+        } else {
+            let shift = 8 * (size - 3);
+            let target = U256::from_u64(mantissa as u64);
+            if shift >= 256 || target.bits() + shift > 256 {
+                return None;
+            }
+            target.shl(shift)
+        };
+        Some(target)
+    }
+
+    // 编码为紧凑格式，会丢失低位精度
+    pub fn to_compact(self) -> u32 {
+        let mut size = self.bits().div_ceil(8);
+        let mut mantissa = if size <= 3 {
+            (self.low_u64() << (8 * (3 - size))) as u32
+        } else {
+            self.shr(8 * (size - 3)).low_u64() as u32
+        };
+        // 尾数最高位是符号位，需要多用一个字节
+        if mantissa & 0x0080_0000 != 0 {
+            mantissa >>= 8;
+            size += 1;
+        }
+        mantissa | (size << 24)
+    }
+
+    // 满足该目标值平均需要尝试的哈希次数: 2^256 / (target + 1)
+    pub fn work(&self) -> U256 {
+        // 2^256 无法用 256 位表示，改写为 (2^256 - target - 1) / (target + 1) + 1
+        match self.checked_add(&U256::from_u64(1)) {
+            Some(divisor) => self.not().div(&divisor).saturating_add(&U256::from_u64(1)),
+            None => U256::from_u64(1),
+        }
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for limb in self.0.iter().rev() {
+            write!(f, "{:016x}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+// JSON 中以 64 位十六进制字符串表示
+impl Serialize for U256 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::difficulty::bits_for_leading_zeros;
+
+    fn pow2(n: u32) -> U256 {
+        U256::from_u64(1).shl(n)
+    }
+
+    #[test]
+    fn compact_round_trips() {
+        for bits in [0x1d00_ffff, 0x1f00_ffff, 0x207f_ffff, 0x1b04_04cb, 0x0312_3456, 0x0500_9234, 0x2100_ffff] {
+            let target = U256::from_compact(bits).unwrap();
+            assert_eq!(target.to_compact(), bits, "{:#010x}", bits);
+        }
+        assert_eq!(U256::ZERO.to_compact(), 0);
+        // 尾数最高位为 1 时多用一个字节
+        assert_eq!(U256::from_u64(0x80).to_compact(), 0x0200_8000);
+        // 低位精度丢失
+        assert_eq!(U256::from_compact(0x0112_3456), Some(U256::from_u64(0x12)));
+        assert_eq!(U256::from_u64(0x12).to_compact(), 0x0112_0000);
+        assert_eq!(U256::from_compact(0x0500_9234), Some(U256::from_u64(0x9234_0000)));
+    }
+
+    #[test]
+    fn compact_rejects_negative_and_overflow() {
+        assert_eq!(U256::from_compact(0x0492_3456), None);
+        assert_eq!(U256::from_compact(0x01fe_dcba), None);
+        // 尾数为 0 时符号位不影响结果
+        assert_eq!(U256::from_compact(0x0480_0000), Some(U256::ZERO));
+        assert_eq!(U256::from_compact(0xff12_3456), None);
+        assert_eq!(U256::from_compact(0x2101_0000), None);
+        assert_eq!(U256::from_compact(0x2200_0001), Some(pow2(248)));
+    }
+
+    #[test]
+    fn leading_zero_targets() {
+        assert_eq!(bits_for_leading_zeros(4), 0x1f00_ffff);
+        assert_eq!(bits_for_leading_zeros(0), U256::MAX.to_compact());
+        assert_eq!(bits_for_leading_zeros(8), 0x1d00_ffff);
+    }
+
+    #[test]
+    fn shifts_cross_limbs() {
+        let one = U256::from_u64(1);
+        assert_eq!(one.shl(64), U256([0, 1, 0, 0]));
+        assert_eq!(one.shl(255).shr(255), one);
+        assert_eq!(U256::from_u64(u64::MAX).shl(60), U256([u64::MAX << 60, u64::MAX >> 4, 0, 0]));
+        assert_eq!(U256([0, 0, 0, 1]).shr(70), U256([0, 1 << 58, 0, 0]));
+        assert_eq!(one.shl(256), U256::ZERO);
+        assert_eq!(U256::MAX.shr(256), U256::ZERO);
+    }
+
+    #[test]
+    fn division() {
+        assert_eq!(U256::from_u64(100).div(&U256::from_u64(7)), U256::from_u64(14));
+        assert_eq!(U256::MAX.div(&U256::from_u64(1)), U256::MAX);
+        assert_eq!(U256::from_u64(12345).shl(200).div(&pow2(200)), U256::from_u64(12345));
+        // 除数最高位为 1
+        assert_eq!(U256::MAX.div(&pow2(255).saturating_add(&U256::from_u64(1))), U256::from_u64(1));
+        assert_eq!(U256::MAX.div(&U256::MAX), U256::from_u64(1));
+        assert_eq!(U256::MAX.div_u64(16), U256::MAX.shr(4));
+    }
+
+    #[test]
+    fn work_for_known_targets() {
+        // 比特币难度 1 的目标值对应的工作量
+        assert_eq!(U256::from_compact(0x1d00_ffff).unwrap().work(), U256::from_u64(0x1_0001_0001));
+        assert_eq!(U256::from_compact(0x207f_ffff).unwrap().work(), U256::from_u64(2));
+        assert_eq!(U256::MAX.work(), U256::from_u64(1));
+        assert_eq!(pow2(255).work(), U256::from_u64(1));
+        assert_eq!(pow2(254).wrapping_sub(&U256::from_u64(1)).work(), U256::from_u64(4));
+    }
+}
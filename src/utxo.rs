@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::amount;
+use crate::encoding::{Decode, Encode, Reader, Writer};
+use crate::{Block, BlockError, Transaction};
+
+// 可选的 UTXO 模型
+//
+// 交易通过 inputs 引用并花费之前交易的输出，再创建新的输出（包括找零）
+// coinbase 和账户模型的转账只有一个隐含输出: 向 receiver 支付 amount
+// 一个输出只能被花费一次，UtxoSet 记录主链上所有尚未花费的输出
+
+// 交易输出的引用: 交易哈希和输出序号
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OutPoint {
+    pub txid: String,
+    pub vout: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TxOut {
+    pub address: String,
+    #[serde(with = "amount::decimal")]
+    pub amount: u64,
+}
+
+impl Encode for OutPoint {
+    fn encode(&self, w: &mut Writer) {
+        w.put_str(&self.txid);
+        w.put_u32(self.vout);
+    }
+}
+
+impl Decode for OutPoint {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str> {
+        Ok(OutPoint {
+            txid: r.get_string()?,
+            vout: r.get_u32()?,
+        })
+    }
+}
+
+impl Encode for TxOut {
+    fn encode(&self, w: &mut Writer) {
+        w.put_str(&self.address);
+        w.put_u64(self.amount);
+    }
+}
+
+impl Decode for TxOut {
+    fn decode(r: &mut Reader<'_>) -> Result<Self, &'static str> {
+        Ok(TxOut {
+            address: r.get_string()?,
+            amount: r.get_u64()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UtxoSet {
+    entries: HashMap<OutPoint, TxOut>,
+    // 每个已接入区块花费掉的输出，断开区块时用来恢复
+    undo: Vec<Vec<(OutPoint, TxOut)>>,
+}
+
+impl UtxoSet {
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&TxOut> {
+        self.entries.get(outpoint)
+    }
+
+    // 属于 address 的所有未花费输出
+    pub fn unspent(&self, address: &str) -> Vec<(OutPoint, TxOut)> {
+        let mut outputs: Vec<(OutPoint, TxOut)> = self
+            .entries
+            .iter()
+            .filter(|(_, out)| out.address == address)
+            .map(|(outpoint, out)| (outpoint.clone(), out.clone()))
+            .collect();
+        outputs.sort_by(|a, b| (&a.0.txid, a.0.vout).cmp(&(&b.0.txid, b.0.vout)));
+        outputs
+    }
+
+    // 校验区块中每笔交易的输入后接入区块，失败时集合保持不变
+    pub fn connect_block(&mut self, block: &Block) -> Result<(), BlockError> {
+        let mut view = UtxoView::new(self, false);
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            view.apply(tx)
+                .map_err(|detail| BlockError::BadTransfer { tx_index, detail })?;
+        }
+        let UtxoView { spent, created, .. } = view;
+
+        let mut undo = Vec::with_capacity(spent.len());
+        for outpoint in &spent {
+            if let Some(out) = self.entries.remove(outpoint) {
+                undo.push((outpoint.clone(), out));
+            }
+        }
+        // 同一区块内创建又花费的输出不进入集合，也不需要恢复
+        for (outpoint, (out, _)) in created {
+            if !spent.contains(&outpoint) {
+                self.entries.insert(outpoint, out);
+            }
+        }
+        self.undo.push(undo);
+        Ok(())
+    }
+
+    // 断开最后接入的区块: 删除它创建的输出，恢复它花费的输出
+    pub fn disconnect_block(&mut self, block: &Block) {
+        for tx in &block.transactions {
+            let txid = tx.hash();
+            for vout in 0..tx.outputs().len() as u32 {
+                self.entries.remove(&OutPoint { txid: txid.clone(), vout });
+            }
+        }
+        for (outpoint, out) in self.undo.pop().unwrap_or_default() {
+            self.entries.insert(outpoint, out);
+        }
+    }
+}
+
+// 在 UtxoSet 之上依次叠加一组交易（区块或内存池）的结果，不修改底层集合
+#[derive(Debug)]
+pub struct UtxoView<'a> {
+    base: &'a UtxoSet,
+    spent: HashSet<OutPoint>,
+    // 新创建的输出及创建它的交易的发送者
+    created: HashMap<OutPoint, (TxOut, String)>,
+    // 内存池中的交易只能花费同一发送者未确认的输出，
+    // 这样按 nonce 打包时父交易总在子交易之前
+    pending: bool,
+}
+
+impl<'a> UtxoView<'a> {
+    pub fn new(base: &'a UtxoSet, pending: bool) -> Self {
+        UtxoView {
+            base,
+            spent: HashSet::new(),
+            created: HashMap::new(),
+            pending,
+        }
+    }
+
+    // 检查交易不会重复创建已有的输出，输入都存在、未被花费、属于发送者，且输入总额等于输出总额加手续费
+    pub fn check(&self, tx: &Transaction) -> Result<(), &'static str> {
+        // 不能重新创建已有的输出 (BIP30)，否则断开区块时原来的输出会一起被删除
+        let txid = tx.hash();
+        let exists = (0..tx.outputs().len() as u32).any(|vout| {
+            let outpoint = OutPoint { txid: txid.clone(), vout };
+            self.created.contains_key(&outpoint) || self.base.get(&outpoint).is_some()
+        });
+        if exists {
+            return Err("Transaction outputs already exist");
+        }
+        if tx.is_coinbase() {
+            return Ok(());
+        }
+        if tx.inputs.is_empty() {
+            return Err("Transaction has no inputs");
+        }
+        let mut seen = HashSet::new();
+        let mut total_in: u64 = 0;
+        for input in &tx.inputs {
+            if !seen.insert(input) || self.spent.contains(input) {
+                return Err("Input already spent");
+            }
+            let out = match (self.created.get(input), self.base.get(input)) {
+                (Some((_, creator)), _) if self.pending && *creator != tx.sender => {
+                    return Err("Cannot spend another sender's unconfirmed output");
+                }
+                (Some((out, _)), _) => out,
+                (None, Some(out)) => out,
+                (None, None) => return Err("Input does not exist or is already spent"),
+            };
+            if out.address != tx.sender {
+                return Err("Input does not belong to the sender");
+            }
+            total_in = total_in.checked_add(out.amount).ok_or("Input total overflows")?;
+        }
+        let total_out = tx
+            .outputs()
+            .iter()
+            .try_fold(tx.fee, |sum, out| sum.checked_add(out.amount))
+            .ok_or("Output total overflows")?;
+        if total_in != total_out {
+            return Err("Inputs must equal outputs plus fee");
+        }
+        Ok(())
+    }
+
+    pub fn apply(&mut self, tx: &Transaction) -> Result<(), &'static str> {
+        self.check(tx)?;
+        self.record(tx);
+        Ok(())
+    }
+
+    // 记入已经通过 check 的交易
+    pub fn record(&mut self, tx: &Transaction) {
+        for input in &tx.inputs {
+            self.spent.insert(input.clone());
+        }
+        let txid = tx.hash();
+        for (vout, out) in tx.outputs().into_iter().enumerate() {
+            let outpoint = OutPoint { txid: txid.clone(), vout: vout as u32 };
+            self.created.insert(outpoint, (out, tx.sender.clone()));
+        }
+    }
+}